@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use ratatui::{Terminal, backend::TestBackend};
+
+use kyutile::state::{CommandResult, State};
+
+const SIZE: usize = 1000;
+const RUNS: u32 = 10;
+
+fn run(state: &mut State, command: &str) {
+    if let CommandResult::Err(err) = state.parse_command(command) {
+        panic!("{}: {}", command, err);
+    }
+    state.commit();
+}
+
+fn bench<F>(name: &str, mut f: F)
+where
+    F: FnMut(),
+{
+    let mut total = Duration::ZERO;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        f();
+        total += start.elapsed();
+    }
+    println!("{:<24} {:>10.3?}", name, total / RUNS);
+}
+
+fn main() {
+    let mut state = State::new().expect("could not create state");
+    run(&mut state, &format!("create {} {}", SIZE, SIZE));
+    let mut terminal = Terminal::new(TestBackend::new(240, 80)).expect("could not create terminal");
+
+    println!("{}x{} map, mean of {} runs", SIZE, SIZE, RUNS);
+    bench("render", || {
+        terminal.draw(|frame| state.draw(frame)).unwrap();
+    });
+    bench("select all", || run(&mut state, "select all"));
+    bench("render with selection", || {
+        terminal.draw(|frame| state.draw(frame)).unwrap();
+    });
+    let mut tile = 0;
+    bench("bucket fill", || {
+        tile = 1 - tile;
+        run(&mut state, &format!("brush {}", tile));
+        run(&mut state, "bucket");
+    });
+    bench("box fill", || {
+        tile = 1 - tile;
+        run(&mut state, &format!("brush {}", tile));
+        run(
+            &mut state,
+            &format!("box 0 0 {} {} fill", SIZE - 1, SIZE - 1),
+        );
+    });
+    bench("fuzzy fill", || {
+        tile = 1 - tile;
+        run(&mut state, &format!("brush {}", tile));
+        run(&mut state, "fuzzy");
+    });
+    bench("fuzzy fill diagonal", || {
+        tile = 1 - tile;
+        run(&mut state, &format!("brush {}", tile));
+        run(&mut state, "fuzzy diagonal");
+    });
+    bench("select none", || run(&mut state, "select none"));
+}
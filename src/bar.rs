@@ -1,7 +1,25 @@
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    path::PathBuf,
+};
+
+use crate::files::state_dir;
+
+const HISTORY_LIMIT: usize = 1000;
+
 #[derive(PartialEq, Eq)]
-pub(crate) struct Input {
+pub struct Input {
     text: String,
     cursor: usize,
+    completion: Option<Completion>,
+    recall: Option<(usize, String)>,
+}
+
+#[derive(PartialEq, Eq)]
+pub(crate) struct Completion {
+    pub(crate) start: usize,
+    pub(crate) candidates: Vec<String>,
+    pub(crate) selected: Option<usize>,
 }
 
 impl Input {
@@ -13,31 +31,45 @@ impl Input {
         self.cursor
     }
 
+    pub(crate) fn completion(&self) -> Option<&Completion> {
+        self.completion.as_ref()
+    }
+
     pub(crate) fn empty() -> Self {
         Input {
             text: "".to_owned(),
             cursor: 0,
+            completion: None,
+            recall: None,
         }
     }
 
     pub(crate) fn move_right(&mut self) {
+        self.completion = None;
+        self.recall = None;
         if self.cursor < self.text.len() {
             self.cursor += 1;
         }
     }
 
     pub(crate) fn move_left(&mut self) {
+        self.completion = None;
+        self.recall = None;
         if self.cursor > 0 {
             self.cursor -= 1;
         }
     }
 
     pub(crate) fn write(&mut self, input: char) {
+        self.completion = None;
+        self.recall = None;
         self.text.insert(self.cursor, input);
         self.cursor += 1;
     }
 
     pub(crate) fn backspace(&mut self) {
+        self.completion = None;
+        self.recall = None;
         if self.cursor > 0 {
             self.cursor -= 1;
             self.text.remove(self.cursor);
@@ -45,8 +77,136 @@ impl Input {
     }
 
     pub(crate) fn delete(&mut self) {
+        self.completion = None;
+        self.recall = None;
         if self.cursor < self.text.len() {
             self.text.remove(self.cursor);
         }
     }
+
+    fn set_text(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+        self.completion = None;
+    }
+
+    pub(crate) fn older(&mut self, history: &CommandHistory) {
+        let (index, prefix) = self
+            .recall
+            .take()
+            .unwrap_or_else(|| (history.entries.len(), self.text.clone()));
+        match history.entries[..index]
+            .iter()
+            .rposition(|entry| entry.starts_with(&prefix))
+        {
+            Some(found) => {
+                self.set_text(history.entries[found].clone());
+                self.recall = Some((found, prefix));
+            }
+            None => self.recall = Some((index, prefix)),
+        }
+    }
+
+    pub(crate) fn newer(&mut self, history: &CommandHistory) {
+        let Some((index, prefix)) = self.recall.take() else {
+            return;
+        };
+        match history
+            .entries
+            .iter()
+            .enumerate()
+            .skip(index + 1)
+            .find(|(_, entry)| entry.starts_with(&prefix))
+        {
+            Some((found, entry)) => {
+                self.set_text(entry.clone());
+                self.recall = Some((found, prefix));
+            }
+            None => self.set_text(prefix),
+        }
+    }
+
+    fn replace_word(&mut self, start: usize, word: &str) {
+        self.text.replace_range(start..self.cursor, word);
+        self.cursor = start + word.len();
+    }
+
+    pub(crate) fn complete<F>(&mut self, forward: bool, candidates: F)
+    where
+        F: FnOnce(&str) -> (usize, Vec<String>),
+    {
+        let completion = match self.completion.take() {
+            Some(completion) => completion,
+            None => {
+                let (start, candidates) = candidates(&self.text[..self.cursor]);
+                match candidates.as_slice() {
+                    [] => return,
+                    [word] => {
+                        let word = if word.ends_with('/') {
+                            word.clone()
+                        } else {
+                            format!("{} ", word)
+                        };
+                        self.replace_word(start, &word);
+                        return;
+                    }
+                    _ => Completion {
+                        start,
+                        candidates,
+                        selected: None,
+                    },
+                }
+            }
+        };
+        let len = completion.candidates.len();
+        let selected = match (completion.selected, forward) {
+            (None, true) => 0,
+            (None, false) => len - 1,
+            (Some(index), true) => (index + 1) % len,
+            (Some(index), false) => (index + len - 1) % len,
+        };
+        self.replace_word(completion.start, &completion.candidates[selected]);
+        self.completion = Some(Completion {
+            selected: Some(selected),
+            ..completion
+        });
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct CommandHistory {
+    pub(crate) entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl CommandHistory {
+    pub(crate) fn load() -> Self {
+        let path = state_dir().map(|dir| dir.join("history"));
+        CommandHistory {
+            entries: path
+                .as_ref()
+                .and_then(|path| read_to_string(path).ok())
+                .map(|text| text.lines().map(str::to_owned).collect())
+                .unwrap_or_default(),
+            path,
+        }
+    }
+
+    pub(crate) fn push(&mut self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| entry != text);
+        self.entries.push(text.to_owned());
+        if self.entries.len() > HISTORY_LIMIT {
+            self.entries.drain(..self.entries.len() - HISTORY_LIMIT);
+        }
+        if let Some(path) = &self.path {
+            let _ = path
+                .parent()
+                .map_or(Ok(()), create_dir_all)
+                .and_then(|_| write(path, self.entries.join("\n")));
+        }
+    }
 }
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, env, sync::OnceLock};
+
+use ratatui::{buffer::Buffer, style::Color};
+
+use crate::tiles::TILES;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+type Rgb = (u8, u8, u8);
+
+const ANSI16_PALETTE: [(u8, Rgb); 16] = [
+    (0, (0, 0, 0)),
+    (1, (205, 0, 0)),
+    (2, (0, 205, 0)),
+    (3, (205, 205, 0)),
+    (4, (0, 0, 238)),
+    (5, (205, 0, 205)),
+    (6, (0, 205, 205)),
+    (7, (229, 229, 229)),
+    (8, (127, 127, 127)),
+    (9, (255, 0, 0)),
+    (10, (0, 255, 0)),
+    (11, (255, 255, 0)),
+    (12, (92, 92, 255)),
+    (13, (255, 0, 255)),
+    (14, (0, 255, 255)),
+    (15, (255, 255, 255)),
+];
+
+const ANSI16_NAMED: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorMode {
+    pub(crate) fn detect() -> ColorMode {
+        let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+        let term = env::var("TERM").unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" || term.contains("direct") {
+            ColorMode::TrueColor
+        } else if term.contains("256") {
+            ColorMode::Ansi256
+        } else {
+            ColorMode::Ansi16
+        }
+    }
+
+    pub fn parse(arg: &str) -> Result<ColorMode, String> {
+        match arg.to_lowercase().as_str() {
+            "truecolor" | "24bit" => Ok(ColorMode::TrueColor),
+            "256" => Ok(ColorMode::Ansi256),
+            "16" => Ok(ColorMode::Ansi16),
+            "auto" => Ok(ColorMode::detect()),
+            _ => Err(format!(
+                "Color mode {} not found, options are truecolor, 256, 16, auto.",
+                arg
+            )),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ColorMode::TrueColor => "truecolor",
+            ColorMode::Ansi256 => "256",
+            ColorMode::Ansi16 => "16",
+        }
+    }
+
+    fn palette(self) -> &'static [(u8, Rgb)] {
+        static ANSI256: OnceLock<Vec<(u8, Rgb)>> = OnceLock::new();
+        match self {
+            ColorMode::TrueColor => &[],
+            ColorMode::Ansi256 => ANSI256.get_or_init(|| {
+                (16..=255)
+                    .map(|i| {
+                        (
+                            i,
+                            if i >= 232 {
+                                let grey = 8 + 10 * (i - 232);
+                                (grey, grey, grey)
+                            } else {
+                                let n = (i - 16) as usize;
+                                (CUBE[n / 36], CUBE[n / 6 % 6], CUBE[n % 6])
+                            },
+                        )
+                    })
+                    .collect()
+            }),
+            ColorMode::Ansi16 => &ANSI16_PALETTE,
+        }
+    }
+
+    fn rgb(self, index: u8) -> Rgb {
+        self.palette()
+            .iter()
+            .find(|(i, _)| *i == index)
+            .map_or((0, 0, 0), |(_, rgb)| *rgb)
+    }
+
+    fn nearest(self, rgb: Rgb) -> u8 {
+        self.palette()
+            .iter()
+            .min_by_key(|(_, other)| distance(rgb, *other))
+            .map_or(0, |(i, _)| *i)
+    }
+
+    fn tiles(self) -> &'static HashMap<Rgb, u8> {
+        static ANSI256: OnceLock<HashMap<Rgb, u8>> = OnceLock::new();
+        static ANSI16: OnceLock<HashMap<Rgb, u8>> = OnceLock::new();
+        let lock = if self == ColorMode::Ansi16 {
+            &ANSI16
+        } else {
+            &ANSI256
+        };
+        lock.get_or_init(|| {
+            let mut free = self.palette().to_vec();
+            let mut tiles = HashMap::new();
+            for tile in TILES.tiles.iter() {
+                let rgb = rgb(tile.2 as u32);
+                if tiles.contains_key(&rgb) {
+                    continue;
+                }
+                let nearest = self.nearest(rgb);
+                let index = match free
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, (_, other))| distance(rgb, *other))
+                {
+                    Some((n, (_, other)))
+                        if distance(rgb, *other) <= 2 * distance(rgb, self.rgb(nearest)) =>
+                    {
+                        free.swap_remove(n).0
+                    }
+                    _ => nearest,
+                };
+                tiles.insert(rgb, index);
+            }
+            tiles
+        })
+    }
+
+    pub(crate) fn convert(self, color: Color) -> Color {
+        match color {
+            Color::Rgb(r, g, b) if self != ColorMode::TrueColor => {
+                let index = self
+                    .tiles()
+                    .get(&(r, g, b))
+                    .copied()
+                    .unwrap_or_else(|| self.nearest((r, g, b)));
+                if self == ColorMode::Ansi16 {
+                    ANSI16_NAMED[index as usize]
+                } else {
+                    Color::Indexed(index)
+                }
+            }
+            _ => color,
+        }
+    }
+
+    pub(crate) fn convert_buffer(self, buf: &mut Buffer) {
+        if self != ColorMode::TrueColor {
+            for cell in buf.content.iter_mut() {
+                cell.fg = self.convert(cell.fg);
+                cell.bg = self.convert(cell.bg);
+            }
+        }
+    }
+}
+
+fn rgb(color: u32) -> Rgb {
+    ((color >> 16) as u8, (color >> 8) as u8, color as u8)
+}
+
+fn distance((r0, g0, b0): Rgb, (r1, g1, b1): Rgb) -> u32 {
+    let mean = (r0 as i32 + r1 as i32) / 2;
+    let (dr, dg, db) = (
+        r0 as i32 - r1 as i32,
+        g0 as i32 - g1 as i32,
+        b0 as i32 - b1 as i32,
+    );
+    ((((512 + mean) * dr * dr) >> 8) + 4 * dg * dg + (((767 - mean) * db * db) >> 8)) as u32
+}
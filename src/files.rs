@@ -1,7 +1,9 @@
-use std::num::ParseIntError;
+use std::{env, num::ParseIntError, path::PathBuf};
 
 use itertools::Itertools;
 
+use crate::grid::Grid;
+
 pub(crate) fn parse_map(input: &str) -> Result<Vec<Vec<i32>>, ParseIntError> {
     input
         .trim()
@@ -10,8 +12,35 @@ pub(crate) fn parse_map(input: &str) -> Result<Vec<Vec<i32>>, ParseIntError> {
         .collect()
 }
 
-pub(crate) fn export_map(map: &[Vec<i32>]) -> String {
-    map.iter()
+pub(crate) fn export_map(map: &Grid) -> String {
+    map.rows()
         .map(|r| r.iter().map(|i| i.to_string()).join(","))
         .join("\n")
 }
+
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join(env!("CARGO_PKG_NAME")))
+}
+
+pub(crate) fn state_dir() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .map(|dir| dir.join(env!("CARGO_PKG_NAME")))
+}
+
+pub(crate) fn config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("config"))
+}
+
+pub(crate) fn fingerprint(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
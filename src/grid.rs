@@ -0,0 +1,206 @@
+use std::ops::{Index, IndexMut};
+
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct Grid {
+    height: usize,
+    width: usize,
+    cells: Vec<i32>,
+}
+
+impl Grid {
+    pub(crate) fn new(height: usize, width: usize, tile: i32) -> Self {
+        Grid {
+            height,
+            width,
+            cells: vec![tile; height * width],
+        }
+    }
+
+    pub(crate) fn from_rows(rows: Vec<Vec<i32>>) -> Self {
+        Grid {
+            height: rows.len(),
+            width: rows.first().map_or(0, Vec::len),
+            cells: rows.concat(),
+        }
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    pub(crate) fn rows(&self) -> impl Iterator<Item = &[i32]> {
+        self.cells.chunks(self.width)
+    }
+
+    pub(crate) fn positions(&self) -> impl Iterator<Item = (usize, usize)> + use<> {
+        let width = self.width;
+        (0..self.height * self.width).map(move |n| (n / width, n % width))
+    }
+}
+
+impl Index<(usize, usize)> for Grid {
+    type Output = i32;
+
+    fn index(&self, (i, j): (usize, usize)) -> &i32 {
+        &self.cells[i * self.width + j]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Grid {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut i32 {
+        &mut self.cells[i * self.width + j]
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct Selection {
+    height: usize,
+    width: usize,
+    bits: Vec<u64>,
+}
+
+impl Selection {
+    pub(crate) fn new(height: usize, width: usize) -> Self {
+        Selection {
+            height,
+            width,
+            bits: vec![0; (height * width).div_ceil(64)],
+        }
+    }
+
+    pub(crate) fn full(height: usize, width: usize) -> Self {
+        let mut select = Selection::new(height, width);
+        select.invert();
+        select
+    }
+
+    pub(crate) fn from_positions<I>(height: usize, width: usize, positions: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        let mut select = Selection::new(height, width);
+        for p in positions {
+            select.insert(p);
+        }
+        select
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    fn bit(&self, (i, j): (usize, usize)) -> Option<(usize, u64)> {
+        if i < self.height && j < self.width {
+            let n = i * self.width + j;
+            Some((n / 64, 1 << (n % 64)))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn contains(&self, p: &(usize, usize)) -> bool {
+        self.bit(*p)
+            .is_some_and(|(word, mask)| self.bits[word] & mask != 0)
+    }
+
+    pub(crate) fn insert(&mut self, p: (usize, usize)) -> bool {
+        match self.bit(p) {
+            Some((word, mask)) if self.bits[word] & mask == 0 => {
+                self.bits[word] |= mask;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn remove(&mut self, p: &(usize, usize)) -> bool {
+        match self.bit(*p) {
+            Some((word, mask)) if self.bits[word] & mask != 0 => {
+                self.bits[word] &= !mask;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn toggle(&mut self, p: (usize, usize)) {
+        if let Some((word, mask)) = self.bit(p) {
+            self.bits[word] ^= mask;
+        }
+    }
+
+    pub(crate) fn invert(&mut self) {
+        for word in &mut self.bits {
+            *word = !*word;
+        }
+        let rest = self.height * self.width % 64;
+        if let Some(last) = self.bits.last_mut()
+            && rest != 0
+        {
+            *last &= (1 << rest) - 1;
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bits.iter().all(|word| *word == 0)
+    }
+
+    fn combine<F>(&self, other: &Selection, f: F) -> Selection
+    where
+        F: Fn(u64, u64) -> u64,
+    {
+        Selection {
+            height: self.height,
+            width: self.width,
+            bits: self
+                .bits
+                .iter()
+                .zip(&other.bits)
+                .map(|(a, b)| f(*a, *b))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn union(&self, other: &Selection) -> Selection {
+        self.combine(other, |a, b| a | b)
+    }
+
+    pub(crate) fn difference(&self, other: &Selection) -> Selection {
+        self.combine(other, |a, b| a & !b)
+    }
+
+    pub(crate) fn symmetric_difference(&self, other: &Selection) -> Selection {
+        self.combine(other, |a, b| a ^ b)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.bits.iter().enumerate().flat_map(move |(n, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    None
+                } else {
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    let k = n * 64 + bit;
+                    Some((k / self.width, k % self.width))
+                }
+            })
+        })
+    }
+}
@@ -0,0 +1,795 @@
+use std::{
+    collections::BTreeMap,
+    fs::{canonicalize, create_dir_all, read_to_string, write},
+    mem::{size_of, swap, take},
+    path::{MAIN_SEPARATOR, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    files::{cache_dir, export_map, fingerprint},
+    grid::Selection,
+    map::{Transform, dot, draw_all, reframe, transform_map},
+    popup::Popup,
+    state::{CommandResult, Map, State, parse_usize},
+    tiles::TILES,
+};
+
+const DEFAULT_LIMIT: usize = 64 << 20;
+const MAX_CELLS: usize = 1 << 28;
+const HEADER: &str = "kyutile undo 2";
+
+pub(crate) enum Change {
+    Tile(usize, usize, i32),
+    Select(usize, usize),
+    Reframe(usize, usize, isize, isize),
+    Transform(Transform),
+}
+
+impl Change {
+    fn apply(&mut self, map: &mut Map) {
+        match self {
+            Change::Tile(i, j, tile) => swap(&mut map.map[(*i, *j)], tile),
+            Change::Select(i, j) => map.select.toggle((*i, *j)),
+            Change::Reframe(height, width, offi, offj) => {
+                let before = (map.map.height(), map.map.width());
+                map.map = reframe(&map.map, *height, *width, *offi, *offj, 0);
+                map.select = reframe_selection(&map.select, *height, *width, *offi, *offj);
+                *self = Change::Reframe(before.0, before.1, -*offi, -*offj);
+            }
+            Change::Transform(transform) => {
+                map.select = transform_selection(&map.select, *transform);
+                map.map = transform_map(&map.map, transform);
+                *transform = transform.inverse();
+            }
+        }
+    }
+
+    fn size(&self) -> usize {
+        size_of::<Change>()
+    }
+}
+
+fn step_size(step: &[Change]) -> usize {
+    step.iter().map(Change::size).sum()
+}
+
+fn replay(step: &[Change], dimensions: (usize, usize), forward: bool) -> Option<(usize, usize)> {
+    let (mut height, mut width) = dimensions;
+    let mut changes = step.iter().collect::<Vec<_>>();
+    if !forward {
+        changes.reverse();
+    }
+    for change in changes {
+        match *change {
+            Change::Tile(i, j, tile) => {
+                if i >= height || j >= width || !TILES.tiles.iter().any(|t| t.0 as i32 == tile) {
+                    return None;
+                }
+            }
+            Change::Select(i, j) => {
+                if i >= height || j >= width {
+                    return None;
+                }
+            }
+            Change::Reframe(h, w, offi, offj) => {
+                if h == 0
+                    || w == 0
+                    || h.checked_mul(w)? > MAX_CELLS
+                    || offi.unsigned_abs() > height.max(h)
+                    || offj.unsigned_abs() > width.max(w)
+                {
+                    return None;
+                }
+                (height, width) = (h, w);
+            }
+            Change::Transform(transform) => (height, width) = transform.dimensions(height, width),
+        }
+    }
+    Some((height, width))
+}
+
+fn step_cells(step: &[Change]) -> usize {
+    step.iter()
+        .map(|change| match change {
+            Change::Tile(..) => 1,
+            _ => 0,
+        })
+        .sum()
+}
+
+pub(crate) fn reframe_selection(
+    select: &Selection,
+    height: usize,
+    width: usize,
+    offi: isize,
+    offj: isize,
+) -> Selection {
+    let shift = |p: usize, off: isize, len: usize| {
+        let n = p as isize + off;
+        (n >= 0 && (n as usize) < len).then_some(n as usize)
+    };
+    Selection::from_positions(
+        height,
+        width,
+        select
+            .iter()
+            .filter_map(|(i, j)| Some((shift(i, offi, height)?, shift(j, offj, width)?))),
+    )
+}
+
+pub(crate) fn transform_selection(select: &Selection, transform: Transform) -> Selection {
+    let (x, y) = (select.height(), select.width());
+    let (nx, ny) = transform.dimensions(x, y);
+    Selection::from_positions(
+        nx,
+        ny,
+        select.iter().map(|(i, j)| transform.apply(x, y, i, j)),
+    )
+}
+
+const TRANSFORMS: [(Transform, &str); 6] = [
+    (Transform::RotateClockwise, "cw"),
+    (Transform::RotateAnticlockwise, "ccw"),
+    (Transform::RotateHalf, "180"),
+    (Transform::FlipHorizontal, "h"),
+    (Transform::FlipVertical, "v"),
+    (Transform::Transpose, "t"),
+];
+
+fn parse_span(arg: &str) -> Result<Result<usize, Duration>, String> {
+    let (number, unit) = arg.split_at(arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len()));
+    let n = parse_usize(number)?;
+    let seconds = match unit {
+        "" => return Ok(Ok(n)),
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => {
+            return Err(format!(
+                "Parse error: {} is not a count or a time, units are s, m, h, d.",
+                arg
+            ));
+        }
+    };
+    (n as u64)
+        .checked_mul(seconds)
+        .map(|secs| Err(Duration::from_secs(secs)))
+        .ok_or(format!("{} is too long.", arg))
+}
+
+fn history_path(path: &str) -> Option<PathBuf> {
+    let path = canonicalize(path).ok()?;
+    Some(
+        cache_dir()?
+            .join("undo")
+            .join(path.to_string_lossy().replace(MAIN_SEPARATOR, "%")),
+    )
+}
+
+fn ago(time: SystemTime) -> String {
+    let secs = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs();
+    match secs {
+        0..60 => format!("{}s ago", secs),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+struct Node {
+    parent: usize,
+    children: Vec<usize>,
+    child: Option<usize>,
+    step: Vec<Change>,
+    cells: usize,
+    time: SystemTime,
+}
+
+pub(crate) struct History {
+    nodes: BTreeMap<usize, Node>,
+    root: usize,
+    current: usize,
+    seq: usize,
+    pending: Vec<Change>,
+    depth: usize,
+    groups: usize,
+    size: usize,
+    pub(crate) limit: usize,
+}
+
+impl History {
+    pub(crate) fn new() -> Self {
+        History {
+            nodes: BTreeMap::from([(
+                0,
+                Node {
+                    parent: 0,
+                    children: Vec::new(),
+                    child: None,
+                    step: Vec::new(),
+                    cells: 0,
+                    time: SystemTime::now(),
+                },
+            )]),
+            root: 0,
+            current: 0,
+            seq: 0,
+            pending: Vec::new(),
+            depth: 0,
+            groups: 0,
+            size: 0,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+
+    fn push(&mut self, step: Vec<Change>) {
+        self.seq += 1;
+        self.size += step_size(&step);
+        let parent = self.nodes.get_mut(&self.current).unwrap();
+        parent.children.push(self.seq);
+        parent.child = Some(self.seq);
+        self.nodes.insert(
+            self.seq,
+            Node {
+                parent: self.current,
+                children: Vec::new(),
+                child: None,
+                cells: step_cells(&step),
+                step,
+                time: SystemTime::now(),
+            },
+        );
+        self.current = self.seq;
+        self.evict();
+    }
+
+    fn traverse(&mut self, map: &mut Map, node: usize, forward: bool) {
+        let step = &mut self.nodes.get_mut(&node).unwrap().step;
+        self.size -= step_size(step);
+        if forward {
+            for change in step.iter_mut() {
+                change.apply(map);
+            }
+        } else {
+            for change in step.iter_mut().rev() {
+                change.apply(map);
+            }
+        }
+        self.size += step_size(step);
+    }
+
+    fn up(&mut self, map: &mut Map) -> bool {
+        if self.current == self.root {
+            false
+        } else {
+            let node = self.current;
+            self.traverse(map, node, false);
+            self.current = self.nodes[&node].parent;
+            self.nodes.get_mut(&self.current).unwrap().child = Some(node);
+            true
+        }
+    }
+
+    fn down(&mut self, map: &mut Map, node: usize) {
+        self.traverse(map, node, true);
+        self.nodes.get_mut(&self.current).unwrap().child = Some(node);
+        self.current = node;
+    }
+
+    fn ancestors(&self, mut node: usize) -> Vec<usize> {
+        let mut ancestors = vec![node];
+        while node != self.root {
+            node = self.nodes[&node].parent;
+            ancestors.push(node);
+        }
+        ancestors
+    }
+
+    fn goto(&mut self, map: &mut Map, target: usize) {
+        let path = self.ancestors(target);
+        while !path.contains(&self.current) {
+            self.up(map);
+        }
+        let start = path.iter().position(|&n| n == self.current).unwrap();
+        for &node in path[..start].iter().rev() {
+            self.down(map, node);
+        }
+    }
+
+    fn remove(&mut self, node: usize) {
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            if let Some(node) = self.nodes.remove(&node) {
+                self.size -= step_size(&node.step);
+                stack.extend(node.children);
+            }
+        }
+    }
+
+    fn evict(&mut self) {
+        let path = self.ancestors(self.current);
+        while self.size > self.limit {
+            let root = &self.nodes[&self.root];
+            if let Some(&branch) = root.children.iter().find(|c| !path.contains(c)) {
+                self.remove(branch);
+                self.nodes
+                    .get_mut(&self.root)
+                    .unwrap()
+                    .children
+                    .retain(|&c| c != branch);
+            } else if let [child] = root.children[..] {
+                self.nodes.remove(&self.root);
+                let node = self.nodes.get_mut(&child).unwrap();
+                self.size -= step_size(&node.step);
+                node.step.clear();
+                node.parent = child;
+                self.root = child;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn time(&self) -> SystemTime {
+        self.nodes[&self.current].time
+    }
+
+    fn serialize(&self, fingerprint: u64) -> String {
+        let mut text = format!(
+            "{}\n{} {} {} {}\n",
+            HEADER, fingerprint, self.root, self.current, self.seq
+        );
+        for (seq, node) in &self.nodes {
+            text += &format!(
+                "n {} {} {} {} {}\n",
+                seq,
+                node.parent,
+                node.child.map_or("-".to_owned(), |child| child.to_string()),
+                node.time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                node.cells
+            );
+            for change in &node.step {
+                text += &match change {
+                    Change::Tile(i, j, tile) => format!("t {} {} {}\n", i, j, tile),
+                    Change::Select(i, j) => format!("s {} {}\n", i, j),
+                    Change::Reframe(height, width, offi, offj) => {
+                        format!("f {} {} {} {}\n", height, width, offi, offj)
+                    }
+                    Change::Transform(transform) => format!(
+                        "x {}\n",
+                        TRANSFORMS.iter().find(|(t, _)| t == transform).unwrap().1
+                    ),
+                };
+            }
+        }
+        text
+    }
+
+    fn deserialize(text: &str, fingerprint: u64, dimensions: (usize, usize)) -> Option<History> {
+        let mut lines = text.lines();
+        if lines.next()? != HEADER {
+            return None;
+        }
+        let mut fields = lines.next()?.split(' ');
+        if fields.next()?.parse::<u64>().ok()? != fingerprint {
+            return None;
+        }
+        let mut fields = fields.map(|field| field.parse::<usize>().ok());
+        let (root, current, seq) = (fields.next()??, fields.next()??, fields.next()??);
+        let mut nodes = BTreeMap::new();
+        let mut last = None;
+        for line in lines {
+            let fields = line.split(' ').collect::<Vec<_>>();
+            let change = match fields[..] {
+                ["n", seq, parent, child, time, cells] => {
+                    last = Some(seq.parse().ok()?);
+                    nodes.insert(
+                        seq.parse().ok()?,
+                        Node {
+                            parent: parent.parse().ok()?,
+                            children: Vec::new(),
+                            child: match child {
+                                "-" => None,
+                                child => Some(child.parse().ok()?),
+                            },
+                            step: Vec::new(),
+                            cells: cells.parse().ok()?,
+                            time: UNIX_EPOCH
+                                .checked_add(Duration::from_secs(time.parse().ok()?))?,
+                        },
+                    );
+                    continue;
+                }
+                ["t", i, j, tile] => {
+                    Change::Tile(i.parse().ok()?, j.parse().ok()?, tile.parse().ok()?)
+                }
+                ["s", i, j] => Change::Select(i.parse().ok()?, j.parse().ok()?),
+                ["f", height, width, offi, offj] => Change::Reframe(
+                    height.parse().ok()?,
+                    width.parse().ok()?,
+                    offi.parse().ok()?,
+                    offj.parse().ok()?,
+                ),
+                ["x", name] => Change::Transform(TRANSFORMS.iter().find(|(_, n)| *n == name)?.0),
+                _ => return None,
+            };
+            nodes.get_mut(&last?)?.step.push(change);
+        }
+        let parents = nodes
+            .iter()
+            .filter(|(seq, _)| **seq != root)
+            .map(|(&seq, node)| (seq, node.parent))
+            .collect::<Vec<_>>();
+        for (seq, parent) in parents {
+            nodes.get_mut(&parent)?.children.push(seq);
+        }
+        if !nodes.contains_key(&root) || !nodes.contains_key(&current) {
+            return None;
+        }
+        let mut sizes = BTreeMap::from([(current, dimensions)]);
+        let mut node = current;
+        while node != root {
+            let parent = nodes.get(&node)?.parent;
+            let size = replay(&nodes[&node].step, sizes[&node], false)?;
+            if sizes.insert(parent, size).is_some() {
+                return None;
+            }
+            node = parent;
+        }
+        let mut stack = vec![root];
+        let mut visited = 0;
+        while let Some(node) = stack.pop() {
+            visited += 1;
+            let Node {
+                child, children, ..
+            } = &nodes[&node];
+            if child.is_some_and(|child| !children.contains(&child)) {
+                return None;
+            }
+            for &child in children {
+                if !sizes.contains_key(&child) {
+                    let size = replay(&nodes[&child].step, sizes[&node], true)?;
+                    sizes.insert(child, size);
+                }
+                stack.push(child);
+            }
+        }
+        if visited != nodes.len() || nodes.keys().any(|&node| node > seq) {
+            return None;
+        }
+        let size = nodes.values().map(|node| step_size(&node.step)).sum();
+        Some(History {
+            nodes,
+            root,
+            current,
+            seq,
+            pending: Vec::new(),
+            depth: 0,
+            groups: 0,
+            size,
+            limit: DEFAULT_LIMIT,
+        })
+    }
+}
+
+impl State {
+    pub(crate) fn set_tile(&mut self, i: usize, j: usize, tile: i32) {
+        if let Some(before) = dot(&mut self.map.map, i, j, tile) {
+            self.history.pending.push(Change::Tile(i, j, before));
+        }
+    }
+
+    pub(crate) fn set_tiles<I>(&mut self, positions: I, tile: i32)
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        for (i, j, before) in draw_all(&mut self.map.map, positions, tile) {
+            self.history.pending.push(Change::Tile(i, j, before));
+        }
+    }
+
+    pub(crate) fn set_selected(&mut self, p: (usize, usize), selected: bool) {
+        if if selected {
+            self.map.select.insert(p)
+        } else {
+            self.map.select.remove(&p)
+        } {
+            self.history.pending.push(Change::Select(p.0, p.1));
+        }
+    }
+
+    pub(crate) fn set_selection(&mut self, select: Selection) {
+        self.history.pending.extend(
+            self.map
+                .select
+                .symmetric_difference(&select)
+                .iter()
+                .map(|(i, j)| Change::Select(i, j)),
+        );
+        self.map.select = select;
+    }
+
+    pub(crate) fn reframe_map(
+        &mut self,
+        height: usize,
+        width: usize,
+        offi: isize,
+        offj: isize,
+        tile: i32,
+    ) {
+        let (x, y) = (self.map.map.height(), self.map.map.width());
+        if (x, y, offi, offj) == (height, width, 0, 0) {
+            return;
+        }
+        let inside = |i: usize, off: isize, len: usize| {
+            let n = i as isize + off;
+            n >= 0 && (n as usize) < len
+        };
+        let lost = self
+            .map
+            .map
+            .positions()
+            .filter(|&(i, j)| !inside(i, offi, height) || !inside(j, offj, width))
+            .collect::<Vec<_>>();
+        self.set_tiles(lost.iter().copied(), 0);
+        for p in lost {
+            self.set_selected(p, false);
+        }
+        self.map.map = reframe(&self.map.map, height, width, offi, offj, 0);
+        self.map.select = reframe_selection(&self.map.select, height, width, offi, offj);
+        self.history
+            .pending
+            .push(Change::Reframe(x, y, -offi, -offj));
+        let added = self
+            .map
+            .map
+            .positions()
+            .filter(|&(i, j)| !inside(i, -offi, x) || !inside(j, -offj, y))
+            .collect::<Vec<_>>();
+        self.set_tiles(added, tile);
+    }
+
+    pub(crate) fn transform_all(&mut self, transform: Transform) {
+        self.map.select = transform_selection(&self.map.select, transform);
+        self.map.map = transform_map(&self.map.map, &transform);
+        self.history
+            .pending
+            .push(Change::Transform(transform.inverse()));
+    }
+
+    pub(crate) fn begin(&mut self) {
+        self.history.depth += 1;
+    }
+
+    pub(crate) fn end(&mut self) {
+        self.history.depth = self.history.depth.saturating_sub(1);
+        self.commit();
+    }
+
+    fn grouping(&self, command: &str) -> Option<CommandResult> {
+        (self.history.depth > 0).then(|| {
+            CommandResult::Err(format!(
+                "Cannot {} while changes are being grouped (use :undo end or :pen up first).",
+                command
+            ))
+        })
+    }
+
+    pub fn commit(&mut self) {
+        if self.history.depth == 0 {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.history.pending.is_empty() {
+            let step = take(&mut self.history.pending);
+            self.history.push(step);
+        }
+    }
+
+    pub(crate) fn undo(&mut self, args: &[&str]) -> CommandResult {
+        let count = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+            None => 1,
+            Some("begin") => {
+                self.history.groups += 1;
+                self.begin();
+                return CommandResult::None;
+            }
+            Some("end") => {
+                if self.history.groups == 0 {
+                    return CommandResult::Err("No undo transaction is open.".to_owned());
+                }
+                self.history.groups -= 1;
+                self.end();
+                return CommandResult::None;
+            }
+            Some(arg) => match parse_usize(arg) {
+                Ok(count) => count.max(1),
+                Err(_) => {
+                    return CommandResult::Err(format!(
+                        "Invalid argument {}, options are begin, end or a count.",
+                        arg
+                    ));
+                }
+            },
+        };
+        if let Some(err) = self.grouping("undo") {
+            return err;
+        }
+        self.flush();
+        if (0..count)
+            .take_while(|_| self.history.up(&mut self.map))
+            .count()
+            > 0
+        {
+            self.reset_cursor();
+            CommandResult::None
+        } else {
+            CommandResult::Err("Undo stack is empty.".to_owned())
+        }
+    }
+
+    pub(crate) fn redo(&mut self, args: &[&str]) -> CommandResult {
+        let count = match args.first().map(|arg| parse_usize(arg)).transpose() {
+            Ok(count) => count.unwrap_or(1).max(1),
+            Err(err) => return CommandResult::Err(err),
+        };
+        if let Some(err) = self.grouping("redo") {
+            return err;
+        }
+        self.flush();
+        let mut redone = 0;
+        while redone < count {
+            let node = &self.history.nodes[&self.history.current];
+            let Some(child) = node.child.or(node.children.last().copied()) else {
+                break;
+            };
+            self.history.down(&mut self.map, child);
+            redone += 1;
+        }
+        if redone > 0 {
+            self.reset_cursor();
+            CommandResult::None
+        } else {
+            CommandResult::Err("Redo stack is empty".to_owned())
+        }
+    }
+
+    pub(crate) fn earlier(&mut self, args: &[&str]) -> CommandResult {
+        if let Some(err) = self.grouping("earlier") {
+            return err;
+        }
+        self.flush();
+        let history = &self.history;
+        let target = match parse_span(args.first().unwrap_or(&"1")) {
+            Ok(Ok(count)) => history
+                .nodes
+                .range(..=history.current.saturating_sub(count))
+                .next_back()
+                .map(|(&seq, _)| seq),
+            Ok(Err(duration)) => {
+                let Some(time) = history.time().checked_sub(duration) else {
+                    return CommandResult::Err(format!("{} is too long.", args[0]));
+                };
+                history
+                    .nodes
+                    .range(..history.current)
+                    .rev()
+                    .find(|(_, node)| node.time <= time)
+                    .map(|(&seq, _)| seq)
+            }
+            Err(err) => return CommandResult::Err(err),
+        }
+        .unwrap_or(history.root);
+        self.history.goto(&mut self.map, target);
+        self.reset_cursor();
+        CommandResult::Ok(format!("Went back to change {}.", target))
+    }
+
+    pub(crate) fn later(&mut self, args: &[&str]) -> CommandResult {
+        if let Some(err) = self.grouping("later") {
+            return err;
+        }
+        self.flush();
+        let history = &self.history;
+        let target = match parse_span(args.first().unwrap_or(&"1")) {
+            Ok(Ok(count)) => history
+                .nodes
+                .range(history.current.saturating_add(count)..)
+                .next()
+                .map(|(&seq, _)| seq),
+            Ok(Err(duration)) => {
+                let Some(time) = history.time().checked_add(duration) else {
+                    return CommandResult::Err(format!("{} is too long.", args[0]));
+                };
+                history
+                    .nodes
+                    .range(history.current..)
+                    .rev()
+                    .find(|(_, node)| node.time <= time)
+                    .map(|(&seq, _)| seq)
+            }
+            Err(err) => return CommandResult::Err(err),
+        }
+        .unwrap_or(*history.nodes.keys().next_back().unwrap());
+        self.history.goto(&mut self.map, target);
+        self.reset_cursor();
+        CommandResult::Ok(format!("Went forward to change {}.", target))
+    }
+
+    pub(crate) fn undolist(&mut self, _: &[&str]) -> CommandResult {
+        self.flush();
+        let history = &self.history;
+        let mut lines = vec![format!(
+            "  {:>6} {:>6} {:>8}  {}",
+            "number", "steps", "cells", "time"
+        )];
+        for (&seq, node) in history.nodes.iter().filter(|(_, n)| n.children.is_empty()) {
+            let branch = history.ancestors(seq);
+            lines.push(format!(
+                "{} {:>6} {:>6} {:>8}  {}",
+                if branch.contains(&history.current) {
+                    '>'
+                } else {
+                    ' '
+                },
+                seq,
+                branch.len() - 1,
+                branch.iter().map(|n| history.nodes[n].cells).sum::<usize>(),
+                ago(node.time)
+            ));
+        }
+        self.popup = Some(Popup::new("Undo list", lines));
+        CommandResult::None
+    }
+
+    pub(crate) fn save_history(&mut self, path: &str) {
+        self.flush();
+        if let Some(file) = history_path(path) {
+            let text = self
+                .history
+                .serialize(fingerprint(&export_map(&self.map.map)));
+            let _ = file
+                .parent()
+                .map_or(Ok(()), create_dir_all)
+                .and_then(|_| write(file, text));
+        }
+    }
+
+    pub(crate) fn load_history(&mut self, path: &str) {
+        let limit = self.history.limit;
+        self.history = history_path(path)
+            .and_then(|file| read_to_string(file).ok())
+            .and_then(|text| {
+                History::deserialize(
+                    &text,
+                    fingerprint(&export_map(&self.map.map)),
+                    (self.map.map.height(), self.map.map.width()),
+                )
+            })
+            .unwrap_or_else(History::new);
+        self.history.limit = limit;
+        self.history.evict();
+    }
+
+    pub(crate) fn undolimit(&mut self, args: &[&str]) -> CommandResult {
+        match parse_usize(args[0]).map(|limit| limit.checked_mul(1 << 20)) {
+            Ok(Some(limit)) => {
+                self.history.limit = limit;
+                self.history.evict();
+                CommandResult::None
+            }
+            Ok(None) => CommandResult::Err(format!("Undo limit {} is too large.", args[0])),
+            Err(err) => CommandResult::Err(err),
+        }
+    }
+}
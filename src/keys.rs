@@ -0,0 +1,88 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+const NAMED: [(KeyCode, &str); 11] = [
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Esc, "Esc"),
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Backspace, "BS"),
+    (KeyCode::Delete, "Del"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::BackTab, "S-Tab"),
+    (KeyCode::Char(' '), "Space"),
+];
+
+pub(crate) fn key_name(key: KeyEvent) -> Option<String> {
+    let name = match key.code {
+        KeyCode::Char(c) if c != ' ' => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        code => NAMED.iter().find(|(k, _)| *k == code)?.1.to_owned(),
+    };
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        Some(format!("C-{}", name))
+    } else {
+        Some(name)
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyEvent> {
+    if let Some(name) = name.strip_prefix("C-")
+        && !name.is_empty()
+    {
+        let key = parse_key(name)?;
+        return Some(KeyEvent::new(
+            key.code,
+            key.modifiers | KeyModifiers::CONTROL,
+        ));
+    }
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ if name == "lt" => KeyCode::Char('<'),
+        _ => match name.strip_prefix('F').and_then(|n| n.parse().ok()) {
+            Some(n) => KeyCode::F(n),
+            None => NAMED.iter().find(|(_, n)| *n == name)?.0,
+        },
+    };
+    Some(if code == KeyCode::BackTab {
+        KeyEvent::new(code, KeyModifiers::SHIFT)
+    } else {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    })
+}
+
+pub(crate) fn format_keys(keys: &[KeyEvent]) -> String {
+    keys.iter()
+        .filter_map(|key| {
+            let name = key_name(*key)?;
+            Some(match name.as_str() {
+                "<" => "<lt>".to_owned(),
+                name if name.chars().count() == 1 => name.to_owned(),
+                name => format!("<{}>", name),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn parse_keys(text: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let name = if c == '<'
+            && let Some(end) = rest.find('>')
+            && end > 1
+        {
+            let name = &rest[1..end];
+            rest = &rest[end + 1..];
+            name
+        } else {
+            let name = &rest[..c.len_utf8()];
+            rest = &rest[c.len_utf8()..];
+            name
+        };
+        keys.push(parse_key(name).ok_or(format!("Parse error: {} is not a key.", name))?);
+    }
+    Ok(keys)
+}
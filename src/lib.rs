@@ -0,0 +1,12 @@
+mod bar;
+pub mod colors;
+mod files;
+mod grid;
+mod history;
+mod keys;
+mod map;
+mod palette;
+mod popup;
+pub mod state;
+mod tiles;
+mod ui;
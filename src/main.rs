@@ -1,37 +1,79 @@
 use std::{
     env,
-    io::{self},
+    io::{self, stdout},
+    panic,
     path::Path,
 };
 
-use state::State;
+use ratatui::crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+};
+
+use kyutile::{
+    colors::ColorMode,
+    state::{Bar, CommandResult, State},
+};
 
-mod bar;
-mod files;
-mod map;
-mod state;
-mod tiles;
-mod ui;
+const HELP: &str = "Usage: kyutile --help|--version|[--colors truecolor|256|16] <path>";
 
-const HELP: &str = "Usage: kyutile --help|--version|<path>";
+struct Session;
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), DisableMouseCapture);
+        ratatui::restore();
+    }
+}
 
 fn main() {
-    let args: Vec<_> = env::args().skip(1).collect();
+    let mut args: Vec<_> = env::args().skip(1).collect();
+    let colors = match args.iter().position(|arg| arg == "--colors") {
+        Some(i) if i + 1 < args.len() => match ColorMode::parse(&args.remove(i + 1)) {
+            Ok(colors) => {
+                args.remove(i);
+                Some(colors)
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                return;
+            }
+        },
+        Some(_) => {
+            eprintln!("{}", HELP);
+            return;
+        }
+        None => None,
+    };
     if args.contains(&"--help".to_owned()) || args.contains(&"-h".to_owned()) {
         println!("{}", HELP)
     } else if args.contains(&"--version".to_owned()) | args.contains(&"-V".to_owned()) {
         println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
     } else {
-        match launch(&args.first()) {
+        match launch(&args.first(), colors) {
             Ok(_) => (),
             Err(err) => eprintln!("An IO error has occurred: {}.", err),
         }
     }
 }
 
-fn launch(arg: &Option<&String>) -> Result<(), io::Error> {
+fn launch(arg: &Option<&String>, colors: Option<ColorMode>) -> Result<(), io::Error> {
     let mut terminal = ratatui::init();
+    let _session = Session;
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = execute!(stdout(), DisableMouseCapture);
+        hook(info);
+    }));
+    execute!(stdout(), EnableMouseCapture)?;
     let mut state: State = State::new()?;
+    state.load_command_history();
+    if let CommandResult::Err(err) = state.load_config() {
+        state.bar = Bar::Err(err);
+    }
+    if let Some(colors) = colors {
+        state.colors = colors;
+    }
     if let Some(path) = arg {
         if Path::new(path.as_str()).exists() {
             let _ = state.open(&[path]);
@@ -44,7 +86,5 @@ fn launch(arg: &Option<&String>) -> Result<(), io::Error> {
         terminal.draw(|frame| state.draw(frame))?;
         state.handle_events()?
     }
-
-    ratatui::restore();
     Ok(())
 }
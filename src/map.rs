@@ -1,4 +1,8 @@
-use std::iter::once;
+use std::{collections::VecDeque, mem::replace};
+
+use itertools::Itertools;
+
+use crate::grid::{Grid, Selection};
 
 pub(crate) fn validate(map: &[Vec<i32>]) -> Result<(), String> {
     if map.is_empty() {
@@ -13,33 +17,184 @@ pub(crate) fn validate(map: &[Vec<i32>]) -> Result<(), String> {
     }
 }
 
-pub(crate) fn dot(map: &mut [Vec<i32>], x: usize, y: usize, tile: i32) -> bool {
-    if map[x][y] == tile {
-        false
+pub(crate) fn dot(map: &mut Grid, x: usize, y: usize, tile: i32) -> Option<i32> {
+    if map[(x, y)] == tile {
+        None
     } else {
-        map[x][y] = tile;
-        true
+        Some(replace(&mut map[(x, y)], tile))
     }
 }
 
-pub(crate) fn draw_all<I>(map: &mut [Vec<i32>], positions: I, tile: i32) -> bool
+pub(crate) fn draw_all<I>(map: &mut Grid, positions: I, tile: i32) -> Vec<(usize, usize, i32)>
 where
     I: IntoIterator<Item = (usize, usize)>,
 {
     positions
         .into_iter()
-        .map(|(i, j)| dot(map, i, j, tile))
-        .reduce(|p, q| p || q)
-        .unwrap_or(false)
+        .filter_map(|(i, j)| dot(map, i, j, tile).map(|before| (i, j, before)))
+        .collect()
 }
 
-pub(crate) fn create(x: usize, y: usize, tile: i32) -> Vec<Vec<i32>> {
-    once(once(tile).cycle().take(y).collect())
-        .cycle()
-        .take(x)
-        .collect()
+pub(crate) fn create(x: usize, y: usize, tile: i32) -> Grid {
+    Grid::new(x, y, tile)
 }
 
 pub(crate) fn in_bounds(lx: usize, ly: usize, x: usize, y: usize) -> bool {
     x < lx && y < ly
 }
+
+pub(crate) fn reframe(map: &Grid, x: usize, y: usize, offi: isize, offj: isize, tile: i32) -> Grid {
+    let mut new_map = create(x, y, tile);
+    for (i, j) in map.positions() {
+        let (ni, nj) = (i as isize + offi, j as isize + offj);
+        if ni >= 0 && nj >= 0 && in_bounds(x, y, ni as usize, nj as usize) {
+            new_map[(ni as usize, nj as usize)] = map[(i, j)];
+        }
+    }
+    new_map
+}
+
+pub(crate) fn line(i0: usize, j0: usize, i1: usize, j1: usize) -> Vec<(usize, usize)> {
+    let (di, dj) = (i1 as isize - i0 as isize, j1 as isize - j0 as isize);
+    let steps = di.abs().max(dj.abs());
+    (0..=steps)
+        .map(|k| {
+            let t = |start: usize, d: isize| {
+                if steps == 0 {
+                    start
+                } else {
+                    (start as isize + (d * k + d.signum() * steps / 2) / steps) as usize
+                }
+            };
+            (t(i0, di), t(j0, dj))
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Transform {
+    RotateClockwise,
+    RotateAnticlockwise,
+    RotateHalf,
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+}
+
+impl Transform {
+    pub(crate) fn dimensions(&self, x: usize, y: usize) -> (usize, usize) {
+        match self {
+            Transform::RotateClockwise | Transform::RotateAnticlockwise | Transform::Transpose => {
+                (y, x)
+            }
+            _ => (x, y),
+        }
+    }
+
+    pub(crate) fn inverse(&self) -> Transform {
+        match self {
+            Transform::RotateClockwise => Transform::RotateAnticlockwise,
+            Transform::RotateAnticlockwise => Transform::RotateClockwise,
+            transform => *transform,
+        }
+    }
+
+    pub(crate) fn apply(&self, x: usize, y: usize, i: usize, j: usize) -> (usize, usize) {
+        match self {
+            Transform::RotateClockwise => (j, x - 1 - i),
+            Transform::RotateAnticlockwise => (y - 1 - j, i),
+            Transform::RotateHalf => (x - 1 - i, y - 1 - j),
+            Transform::FlipHorizontal => (i, y - 1 - j),
+            Transform::FlipVertical => (x - 1 - i, j),
+            Transform::Transpose => (j, i),
+        }
+    }
+}
+
+pub(crate) fn transform_map(map: &Grid, transform: &Transform) -> Grid {
+    let (x, y) = (map.height(), map.width());
+    let (nx, ny) = transform.dimensions(x, y);
+    let mut new_map = create(nx, ny, 0);
+    for (i, j) in map.positions() {
+        new_map[transform.apply(x, y, i, j)] = map[(i, j)];
+    }
+    new_map
+}
+
+pub(crate) enum Footprint {
+    Square,
+    Circle,
+    Diamond,
+}
+
+impl Footprint {
+    pub(crate) fn contains(&self, radius: usize, di: isize, dj: isize) -> bool {
+        let r = radius as isize;
+        match self {
+            Footprint::Square => di.abs() <= r && dj.abs() <= r,
+            Footprint::Circle => di * di + dj * dj <= r * r + r,
+            Footprint::Diamond => di.abs() + dj.abs() <= r,
+        }
+    }
+
+    pub(crate) fn outline(&self, radius: usize, di: isize, dj: isize) -> bool {
+        self.contains(radius, di, dj)
+            && [(-1, 0), (0, -1), (1, 0), (0, 1)]
+                .iter()
+                .any(|(ei, ej)| !self.contains(radius, di + ei, dj + ej))
+    }
+}
+
+pub(crate) fn footprint(footprint: &Footprint, radius: usize) -> Vec<(isize, isize)> {
+    let r = radius as isize;
+    (-r..=r)
+        .cartesian_product(-r..=r)
+        .filter(|&(di, dj)| footprint.contains(radius, di, dj))
+        .collect()
+}
+
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, 0),
+    (0, -1),
+    (1, 0),
+    (0, 1),
+    (-1, -1),
+    (-1, 1),
+    (1, -1),
+    (1, 1),
+];
+
+pub(crate) fn flood<F>(
+    map: &Grid,
+    start: (usize, usize),
+    length: Option<usize>,
+    diagonal: bool,
+    same: F,
+) -> Selection
+where
+    F: Fn(i32) -> bool,
+{
+    let (x, y) = (map.height(), map.width());
+    let mut reached = Selection::new(x, y);
+    if length == Some(0) {
+        return reached;
+    }
+    let neighbours = &NEIGHBOURS[..if diagonal { 8 } else { 4 }];
+    let mut queue = VecDeque::from([(start, 1)]);
+    reached.insert(start);
+    while let Some(((i, j), steps)) = queue.pop_front() {
+        if length.is_some_and(|length| steps >= length) {
+            continue;
+        }
+        for (di, dj) in neighbours {
+            let (ni, nj) = (i as isize + di, j as isize + dj);
+            if ni >= 0 && nj >= 0 && in_bounds(x, y, ni as usize, nj as usize) {
+                let p = (ni as usize, nj as usize);
+                if same(map[p]) && reached.insert(p) {
+                    queue.push_back((p, steps + 1));
+                }
+            }
+        }
+    }
+    reached
+}
@@ -0,0 +1,37 @@
+use crate::tiles::TILES;
+
+pub(crate) struct Palette {
+    pub(crate) index: usize,
+    pub(crate) focus: bool,
+}
+
+impl Palette {
+    pub(crate) fn new(tile: Option<i32>) -> Self {
+        Palette {
+            index: tile.and_then(index_of).unwrap_or(0),
+            focus: false,
+        }
+    }
+
+    pub(crate) fn tile(&self) -> i32 {
+        TILES.tiles[self.index].0 as i32
+    }
+
+    pub(crate) fn down(&mut self) {
+        if self.index + 1 < TILES.tiles.len() {
+            self.index += 1;
+        }
+    }
+
+    pub(crate) fn up(&mut self) {
+        self.index = self.index.saturating_sub(1);
+    }
+
+    pub(crate) fn offset(&self, height: usize) -> usize {
+        (self.index + 1).saturating_sub(height)
+    }
+}
+
+pub(crate) fn index_of(tile: i32) -> Option<usize> {
+    TILES.tiles.iter().position(|t| t.0 as i32 == tile)
+}
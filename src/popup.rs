@@ -0,0 +1,25 @@
+pub(crate) struct Popup {
+    pub(crate) title: String,
+    pub(crate) lines: Vec<String>,
+    pub(crate) scroll: usize,
+}
+
+impl Popup {
+    pub(crate) fn new(title: &str, lines: Vec<String>) -> Self {
+        Popup {
+            title: title.to_owned(),
+            lines,
+            scroll: 0,
+        }
+    }
+
+    pub(crate) fn scroll_down(&mut self) {
+        if self.scroll + 1 < self.lines.len() {
+            self.scroll += 1;
+        }
+    }
+
+    pub(crate) fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+}
@@ -1,31 +1,41 @@
 use std::{
     collections::{HashMap, HashSet},
-    fs::{read_to_string, write},
+    fs::{create_dir_all, read_dir, read_to_string, write},
     io,
+    mem::replace,
 };
 
 use itertools::Itertools;
-use ratatui::crossterm::event::KeyCode;
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+};
 
 use crate::{
-    bar::Input,
-    map::{create, draw_all, in_bounds, validate},
-    tiles::TILES,
+    bar::{CommandHistory, Input},
+    map::{Transform, create, flood, in_bounds, validate},
+    tiles::{TILES, family},
 };
 use crate::{
-    files::{export_map, parse_map},
-    map::dot,
+    colors::ColorMode,
+    files::{config_path, export_map, parse_map},
+    grid::{Grid, Selection},
+    history::History,
+    keys::{format_keys, parse_keys},
+    map::{Footprint, footprint},
+    palette::Palette,
+    popup::Popup,
 };
 
 #[derive(PartialEq, Eq)]
-pub(crate) enum Bar {
+pub enum Bar {
     Closed,
     Input(Input),
     Err(String),
     Ok(String),
 }
 
-pub(crate) enum CommandResult {
+pub enum CommandResult {
     None,
     Err(String),
     Ok(String),
@@ -37,16 +47,59 @@ pub(crate) enum Pen {
     Down,
 }
 
+#[derive(Clone, Copy)]
 pub(crate) enum Brush {
     Add,
     Subtract,
     Tile(i32),
 }
 
+const MAX_SIZE: usize = 100;
+const MAX_ZOOM: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Zoom {
+    Wide,
+    Square,
+    Blocks(usize),
+}
+
+impl Zoom {
+    fn zoom_in(self) -> Zoom {
+        match self {
+            Zoom::Wide | Zoom::Square => Zoom::Wide,
+            Zoom::Blocks(1) => Zoom::Square,
+            Zoom::Blocks(n) => Zoom::Blocks(n / 2),
+        }
+    }
+
+    fn zoom_out(self) -> Zoom {
+        match self {
+            Zoom::Wide => Zoom::Square,
+            Zoom::Square => Zoom::Blocks(1),
+            Zoom::Blocks(n) => Zoom::Blocks((n * 2).min(MAX_ZOOM)),
+        }
+    }
+
+    pub(crate) fn border(self) -> isize {
+        match self {
+            Zoom::Wide | Zoom::Square => 1,
+            Zoom::Blocks(_) => 0,
+        }
+    }
+
+    pub(crate) fn step(self) -> usize {
+        match self {
+            Zoom::Wide | Zoom::Square => 1,
+            Zoom::Blocks(n) => n,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq)]
 pub(crate) struct Map {
-    pub(crate) map: Vec<Vec<i32>>,
-    pub(crate) select: HashSet<(usize, usize)>,
+    pub(crate) map: Grid,
+    pub(crate) select: Selection,
 }
 
 pub(crate) struct Clipboard {
@@ -55,20 +108,87 @@ pub(crate) struct Clipboard {
     pub(crate) offsety: usize,
 }
 
-pub(crate) struct State {
+pub(crate) struct Floating {
+    pub(crate) content: HashMap<(usize, usize), i32>,
+    pub(crate) offsetx: isize,
+    pub(crate) offsety: isize,
+}
+
+pub(crate) struct Repeat {
+    name: &'static str,
+    args: Vec<String>,
+    cursor: (usize, usize),
+    brush: Brush,
+}
+
+const LIFTED: [&str; 19] = [
+    "open",
+    "open!",
+    "dot",
+    "bucket",
+    "paste",
+    "box",
+    "ellipse",
+    "fuzzy",
+    "create",
+    "resize",
+    "crop",
+    "pad",
+    "map",
+    "selection",
+    "undo",
+    "redo",
+    "earlier",
+    "later",
+    "repeat",
+];
+
+const MAX_MACRO_DEPTH: usize = 100;
+const MAX_MACRO_KEYS: usize = 100_000;
+const MAX_SOURCE_DEPTH: usize = 16;
+
+const REPEATABLE: [&str; 6] = ["dot", "bucket", "paste", "box", "ellipse", "fuzzy"];
+
+pub struct State {
     pub(crate) argument: usize,
-    pub(crate) bar: Bar,
+    pub(crate) background: i32,
+    pub(crate) camerax: isize,
+    pub(crate) cameray: isize,
+    pub bar: Bar,
     pub(crate) brush: Brush,
     pub(crate) clipboard: Option<Clipboard>,
+    pub colors: ColorMode,
+    pub(crate) commands: CommandHistory,
     pub(crate) cursorx: usize,
     pub(crate) cursory: usize,
-    pub(crate) exit: bool,
-    pub(crate) last_saved: Option<Vec<Vec<i32>>>,
+    direction: Direction,
+    pub(crate) drag: Option<(usize, usize)>,
+    pub exit: bool,
+    pub(crate) floating: Option<Floating>,
+    pub(crate) glyphs: bool,
+    pub(crate) grid: usize,
+    pub(crate) last_saved: Option<Grid>,
+    pub(crate) macros: HashMap<char, Vec<KeyEvent>>,
     pub(crate) map: Map,
-    pub(crate) path: Option<String>,
+    pub(crate) map_area: Rect,
+    pub(crate) palette: Option<Palette>,
+    pub path: Option<String>,
     pub(crate) pen: Pen,
-    redo_stack: Vec<Map>,
-    undo_stack: Vec<Map>,
+    pub(crate) playing: usize,
+    played: usize,
+    sourcing: usize,
+    pub(crate) popup: Option<Popup>,
+    pub(crate) recording: Option<(char, Vec<KeyEvent>)>,
+    pub(crate) repeat: Option<Repeat>,
+    pub(crate) size: usize,
+    pub(crate) footprint: Footprint,
+    pub(crate) history: History,
+    pub(crate) zoom: Zoom,
+    pub(crate) minimap: bool,
+    pub(crate) pending: Vec<String>,
+    pub(crate) rulers: bool,
+    pub(crate) scrolloff: usize,
+    pub(crate) view: (usize, usize),
 }
 
 struct Command {
@@ -76,6 +196,9 @@ struct Command {
     aliases: &'static [&'static str],
     argsmin: usize,
     argsmax: usize,
+    usage: &'static str,
+    completion: &'static str,
+    description: &'static str,
     function: fn(&mut State, &[&str]) -> CommandResult,
 }
 
@@ -85,6 +208,8 @@ impl Command {
         aliases: &'static [&'static str],
         argsmin: usize,
         argsmax: usize,
+        usage: &'static str,
+        description: &'static str,
         function: fn(&mut State, &[&str]) -> CommandResult,
     ) -> Self {
         Command {
@@ -92,11 +217,27 @@ impl Command {
             aliases,
             argsmin,
             argsmax,
+            usage,
+            completion: usage,
+            description,
             function,
         }
     }
+
+    const fn completing(self, completion: &'static str) -> Self {
+        Command { completion, ..self }
+    }
+
+    fn arguments(&self) -> String {
+        if self.argsmin == self.argsmax {
+            self.argsmin.to_string()
+        } else {
+            format!("{}-{}", self.argsmin, self.argsmax)
+        }
+    }
 }
 
+#[derive(Clone, Copy)]
 enum Direction {
     Left,
     Down,
@@ -117,7 +258,70 @@ fn parse_direction(arg: &str) -> Result<Direction, String> {
     }
 }
 
-fn parse_usize(arg: &str) -> Result<usize, String> {
+fn margin(scrolloff: usize, visible: usize) -> isize {
+    scrolloff.min(visible.saturating_sub(1) / 2) as isize
+}
+
+fn parse_register(arg: &str) -> Result<char, String> {
+    match arg.chars().exactly_one() {
+        Ok(c) if c.is_ascii_alphanumeric() => Ok(c),
+        _ => Err(format!(
+            "Parse error: {} is not a register, registers are letters and digits.",
+            arg
+        )),
+    }
+}
+
+fn parse_anchor(arg: &str) -> Result<(usize, usize), String> {
+    match arg.to_lowercase().as_str() {
+        "nw" => Ok((0, 0)),
+        "n" => Ok((1, 0)),
+        "ne" => Ok((2, 0)),
+        "w" => Ok((0, 1)),
+        "c" | "center" => Ok((1, 1)),
+        "e" => Ok((2, 1)),
+        "sw" => Ok((0, 2)),
+        "s" => Ok((1, 2)),
+        "se" => Ok((2, 2)),
+        _ => Err(format!(
+            "Parse error: {} is not an anchor, options are nw, n, ne, w, c, e, sw, s, se.",
+            arg
+        )),
+    }
+}
+
+fn parse_fuzzy(args: &[&str]) -> Result<(Option<usize>, bool, bool), String> {
+    let (mut length, mut diagonal, mut loose) = (None, false, false);
+    for arg in args {
+        match *arg {
+            "diagonal" => diagonal = true,
+            "family" => loose = true,
+            arg if length.is_some() => {
+                return Err(format!(
+                    "Invalid argument {}, the length is already given, options are diagonal, family.",
+                    arg
+                ));
+            }
+            arg => {
+                length = Some(parse_usize(arg).map_err(|_| {
+                    format!(
+                        "Invalid argument {}, options are a length, diagonal, family.",
+                        arg
+                    )
+                })?)
+            }
+        }
+    }
+    Ok((length, diagonal, loose))
+}
+
+fn parse_count(args: &[&str], index: usize) -> Result<usize, String> {
+    args.get(index)
+        .map_or(Ok(1), |arg| parse_usize(arg))
+        .map(|count| count.max(1))
+}
+
+pub(crate) fn parse_usize(arg: &str) -> Result<usize, String> {
     arg.parse()
         .map_err(|_| format!("Parse error: {} is not an integer.", arg))
 }
@@ -143,24 +347,50 @@ fn parse_tile(tile: &str) -> Result<i32, String> {
 }
 
 impl State {
-    pub(crate) fn new() -> Result<State, io::Error> {
+    pub fn new() -> Result<State, io::Error> {
         Ok(State {
             map: Map {
                 map: create(11, 11, 0),
-                select: HashSet::new(),
+                select: Selection::new(11, 11),
             },
             clipboard: None,
+            floating: None,
             last_saved: None,
             exit: false,
             path: None,
             pen: Pen::Up,
+            popup: None,
+            repeat: None,
+            size: 0,
+            footprint: Footprint::Square,
             cursorx: 0,
             cursory: 0,
+            direction: Direction::Right,
             argument: 0,
+            background: 0,
             brush: Brush::Tile(0),
             bar: Bar::Closed,
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            history: History::new(),
+            zoom: Zoom::Wide,
+            minimap: false,
+            pending: Vec::new(),
+            scrolloff: 2,
+            view: (0, 0),
+            camerax: -1,
+            cameray: -1,
+            drag: None,
+            map_area: Rect::default(),
+            palette: None,
+            colors: ColorMode::detect(),
+            glyphs: false,
+            grid: 0,
+            rulers: true,
+            commands: CommandHistory::default(),
+            macros: HashMap::new(),
+            recording: None,
+            playing: 0,
+            played: 0,
+            sourcing: 0,
         })
     }
 
@@ -171,11 +401,6 @@ impl State {
         }
     }
 
-    fn push_undo(&mut self, map_clone: Map) {
-        self.undo_stack.push(map_clone);
-        self.redo_stack.clear();
-    }
-
     pub(crate) fn append_argument(&mut self, digit: u8) {
         self.argument = (if let Some(p) = self.argument.checked_mul(10) {
             p.checked_add(digit.into())
@@ -185,7 +410,7 @@ impl State {
         .unwrap_or(self.argument)
     }
 
-    pub(crate) fn open(&mut self, args: &[&str]) -> CommandResult {
+    pub fn open(&mut self, args: &[&str]) -> CommandResult {
         if self.modified() {
             CommandResult::Err(
                 "Unsaved changes (use :o! to discard them and open another file or :w to save them).".to_owned(),
@@ -206,12 +431,14 @@ impl State {
         };
         match validate(&map) {
             Ok(_) => {
+                let map = Grid::from_rows(map);
                 self.map = Map {
+                    select: Selection::new(map.height(), map.width()),
                     map: map.clone(),
-                    select: HashSet::new(),
                 };
                 self.path = Some(path.to_owned());
                 self.last_saved = Some(map);
+                self.load_history(path);
                 CommandResult::Ok(format!("Opened {}.", path))
             }
             Err(err) => CommandResult::Err(format!("Could not validate map: {}", err)),
@@ -225,13 +452,68 @@ impl State {
         match &self.path {
             None => CommandResult::Err("No path set (use :w <path>).".to_owned()),
             Some(path) => {
-                write(path, export_map(&self.map.map)).unwrap();
+                let path = path.clone();
+                write(&path, export_map(&self.map.map)).unwrap();
                 self.last_saved = Some(self.map.map.clone());
+                self.save_history(&path);
                 CommandResult::Ok(format!("Written to {}.", path))
             }
         }
     }
 
+    pub(crate) fn source(&mut self, args: &[&str]) -> CommandResult {
+        let path = args[0];
+        let script = match read_to_string(path) {
+            Ok(script) => script,
+            Err(_) => return CommandResult::Err(format!("Could not open file {}.", path)),
+        };
+        match self.run_script(path, &script) {
+            Some(err) => CommandResult::Err(err),
+            None => CommandResult::Ok(format!("Sourced {}.", path)),
+        }
+    }
+
+    fn run_script(&mut self, path: &str, script: &str) -> Option<String> {
+        if self.sourcing >= MAX_SOURCE_DEPTH {
+            return Some("Scripts are sourced too deeply.".to_owned());
+        }
+        self.sourcing += 1;
+        self.begin();
+        let mut result = None;
+        for (n, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            if let CommandResult::Err(err) =
+                self.parse_command(line.strip_prefix(':').unwrap_or(line))
+            {
+                result = Some(format!("Line {} of {}: {}", n + 1, path, err));
+                break;
+            }
+        }
+        self.end();
+        self.sourcing -= 1;
+        result
+    }
+
+    pub fn load_command_history(&mut self) {
+        self.commands = CommandHistory::load();
+    }
+
+    pub fn load_config(&mut self) -> CommandResult {
+        let Some(path) = config_path() else {
+            return CommandResult::None;
+        };
+        match read_to_string(&path) {
+            Ok(script) => match self.run_script(&path.to_string_lossy(), &script) {
+                Some(err) => CommandResult::Err(err),
+                None => CommandResult::None,
+            },
+            Err(_) => CommandResult::None,
+        }
+    }
+
     pub(crate) fn quit(&mut self, _: &[&str]) -> CommandResult {
         if self.modified() {
             CommandResult::Err(
@@ -258,34 +540,85 @@ impl State {
         }
     }
     pub(crate) fn bucket(&mut self, _: &[&str]) -> CommandResult {
-        let map_clone = self.map.clone();
         if let Brush::Tile(tile) = self.brush {
-            if draw_all(&mut self.map.map, self.map.select.clone(), tile) {
-                self.push_undo(map_clone);
+            self.set_tiles(self.map.select.iter().collect::<Vec<_>>(), tile);
+        }
+        CommandResult::None
+    }
+
+    pub(crate) fn dot(&mut self, args: &[&str]) -> CommandResult {
+        let count = match parse_count(args, 0) {
+            Ok(count) => count,
+            Err(err) => return CommandResult::Err(err),
+        };
+        let mut positions = vec![(self.cursory, self.cursorx)];
+        for _ in 1..count {
+            if !self.step(self.direction, 1) {
+                break;
             }
+            positions.push((self.cursory, self.cursorx));
         }
+        self.paint(&positions);
         CommandResult::None
     }
 
-    pub(crate) fn dot(&mut self, _: &[&str]) -> CommandResult {
-        let map_clone = self.map.clone();
+    pub(crate) fn paint(&mut self, positions: &[(usize, usize)]) {
+        let (lx, ly) = (self.map.map.height(), self.map.map.width());
+        let offsets = footprint(&self.footprint, self.size);
+        let positions = positions
+            .iter()
+            .cartesian_product(&offsets)
+            .map(|(&(i, j), &(di, dj))| (i as isize + di, j as isize + dj))
+            .filter(|&(i, j)| i >= 0 && j >= 0 && in_bounds(lx, ly, i as usize, j as usize))
+            .map(|(i, j)| (i as usize, j as usize))
+            .collect::<HashSet<_>>();
+        self.apply_brush(positions);
+    }
+
+    fn apply_brush<I>(&mut self, positions: I)
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
         match self.brush {
-            Brush::Tile(tile) => {
-                if dot(&mut self.map.map, self.cursory, self.cursorx, tile) {
-                    self.push_undo(map_clone);
-                }
-            }
+            Brush::Tile(tile) => self.set_tiles(positions, tile),
             Brush::Add => {
-                if self.map.select.insert((self.cursory, self.cursorx)) {
-                    self.push_undo(map_clone);
+                for p in positions {
+                    self.set_selected(p, true);
                 }
             }
             Brush::Subtract => {
-                if self.map.select.remove(&(self.cursory, self.cursorx)) {
-                    self.push_undo(map_clone);
+                for p in positions {
+                    self.set_selected(p, false);
                 }
             }
         }
+    }
+
+    pub(crate) fn size(&mut self, args: &[&str]) -> CommandResult {
+        let size = match parse_usize(args[0]) {
+            Ok(size) if size <= MAX_SIZE => size,
+            Ok(size) => {
+                return CommandResult::Err(format!(
+                    "Brush size {} is too large, the maximum is {}.",
+                    size, MAX_SIZE
+                ));
+            }
+            Err(err) => return CommandResult::Err(err),
+        };
+        if let Some(arg) = args.get(1) {
+            self.footprint = match arg.to_lowercase().as_str() {
+                "square" => Footprint::Square,
+                "circle" => Footprint::Circle,
+                "diamond" => Footprint::Diamond,
+                _ => {
+                    return CommandResult::Err(format!(
+                        "Footprint {} not found, options are square, circle, diamond.",
+                        arg
+                    ));
+                }
+            }
+        }
+        self.size = size;
         CommandResult::None
     }
 
@@ -310,10 +643,16 @@ impl State {
     pub(crate) fn pen(&mut self, args: &[&str]) -> CommandResult {
         match args[0].to_lowercase().as_str() {
             "up" => {
+                if self.pen == Pen::Down {
+                    self.end();
+                }
                 self.pen = Pen::Up;
                 CommandResult::None
             }
             "down" => {
+                if self.pen == Pen::Up {
+                    self.begin();
+                }
                 self.pen = Pen::Down;
                 CommandResult::None
             }
@@ -332,16 +671,26 @@ impl State {
                 Err(err) => return CommandResult::Err(err),
             },
         };
-        self.move_cursor(
-            match parse_direction(args[0]) {
-                Ok(direction) => direction,
-                Err(err) => return CommandResult::Err(err),
-            },
-            distance,
-        );
+        self.direction = match parse_direction(args[0]) {
+            Ok(direction) => direction,
+            Err(err) => return CommandResult::Err(err),
+        };
+        self.move_cursor(self.direction, distance);
         CommandResult::None
     }
 
+    fn step(&mut self, direction: Direction, distance: usize) -> bool {
+        let before = (self.cursorx, self.cursory);
+        let (x, y) = (self.map.map.width(), self.map.map.height());
+        match direction {
+            Direction::Left => self.cursorx = self.cursorx.saturating_sub(distance),
+            Direction::Down => self.cursory = (self.cursory + distance).min(y - 1),
+            Direction::Up => self.cursory = self.cursory.saturating_sub(distance),
+            Direction::Right => self.cursorx = (self.cursorx + distance).min(x - 1),
+        }
+        (self.cursorx, self.cursory) != before
+    }
+
     fn move_cursor(&mut self, direction: Direction, distance: usize) {
         let (nx, ny, positions) = match direction {
             Direction::Left => {
@@ -355,7 +704,7 @@ impl State {
                 )
             }
             Direction::Down => {
-                let ny = (self.cursory + distance).min(self.map.map.len() - 1);
+                let ny = (self.cursory + distance).min(self.map.map.height() - 1);
                 (
                     self.cursorx,
                     ny,
@@ -371,7 +720,7 @@ impl State {
                 )
             }
             Direction::Right => {
-                let nx = (self.cursorx + distance).min(self.map.map[0].len() - 1);
+                let nx = (self.cursorx + distance).min(self.map.map.width() - 1);
                 (
                     nx,
                     self.cursory,
@@ -382,40 +731,24 @@ impl State {
         self.cursorx = nx;
         self.cursory = ny;
         if self.pen == Pen::Down {
-            let map_clone = self.map.clone();
-            match self.brush {
-                Brush::Tile(tile) => {
-                    for &(i, j) in &positions {
-                        dot(&mut self.map.map, i, j, tile);
-                    }
-                }
-                Brush::Add => self.map.select.extend(positions),
-                Brush::Subtract => {
-                    for p in positions {
-                        self.map.select.remove(&p);
-                    }
-                }
-            }
-            if self.map != map_clone {
-                self.push_undo(map_clone);
-            }
+            self.paint(&positions);
         }
     }
 
     pub(crate) fn edge(&mut self, args: &[&str]) -> CommandResult {
-        match match parse_direction(args[0]) {
+        let parts = match parse_count(args, 1) {
+            Ok(parts) => parts,
+            Err(err) => return CommandResult::Err(err),
+        };
+        let direction = match parse_direction(args[0]) {
             Ok(direction) => direction,
             Err(err) => return CommandResult::Err(err),
-        } {
-            Direction::Left => self.move_cursor(Direction::Left, self.cursorx),
-            Direction::Down => {
-                self.move_cursor(Direction::Down, self.map.map[0].len() - self.cursory)
-            }
-            Direction::Up => self.move_cursor(Direction::Up, self.cursory),
-            Direction::Right => {
-                self.move_cursor(Direction::Right, self.map.map[0].len() - self.cursorx)
-            }
         };
+        let length = match direction {
+            Direction::Left | Direction::Right => self.map.map.width(),
+            Direction::Down | Direction::Up => self.map.map.height(),
+        };
+        self.move_cursor(direction, (length / parts).max(1));
         CommandResult::None
     }
 
@@ -428,7 +761,7 @@ impl State {
             Ok(j) => j,
             Err(err) => return CommandResult::Err(err),
         };
-        if in_bounds(self.map.map.len(), self.map.map[0].len(), i, j) {
+        if in_bounds(self.map.map.height(), self.map.map.width(), i, j) {
             self.cursorx = i;
             self.cursory = j;
             CommandResult::None
@@ -437,45 +770,119 @@ impl State {
         }
     }
 
-    pub(crate) fn pick(&mut self, _: &[&str]) -> CommandResult {
-        self.brush = Brush::Tile(self.map.map[self.cursory][self.cursorx]);
+    fn clamp_camera(&mut self) {
+        let border = self.zoom.border();
+        let clamp = |camera: isize, len: usize, visible: usize| {
+            camera
+                .min(len as isize + border - visible as isize)
+                .max(-border)
+        };
+        self.cameray = clamp(self.cameray, self.map.map.height(), self.view.0);
+        self.camerax = clamp(self.camerax, self.map.map.width(), self.view.1);
+    }
+
+    pub(crate) fn follow(&mut self, rows: usize, cols: usize) {
+        self.view = (rows, cols);
+        let scrolloff = self.scrolloff;
+        let follow = |camera: isize, cursor: usize, visible: usize| {
+            let (cursor, margin) = (cursor as isize, margin(scrolloff, visible));
+            camera
+                .max(cursor + margin + 1 - visible as isize)
+                .min(cursor - margin)
+        };
+        self.cameray = follow(self.cameray, self.cursory, rows);
+        self.camerax = follow(self.camerax, self.cursorx, cols);
+        self.clamp_camera();
+    }
+
+    pub(crate) fn scroll(&mut self, args: &[&str]) -> CommandResult {
+        let distance = match args.get(1) {
+            None => 1,
+            Some(arg) => match parse_usize(arg) {
+                Ok(distance) => distance,
+                Err(err) => return CommandResult::Err(err),
+            },
+        };
+        let distance = distance
+            .saturating_mul(self.zoom.step())
+            .min(isize::MAX as usize) as isize;
+        match parse_direction(args[0]) {
+            Ok(Direction::Left) => self.camerax = self.camerax.saturating_sub(distance),
+            Ok(Direction::Down) => self.cameray = self.cameray.saturating_add(distance),
+            Ok(Direction::Up) => self.cameray = self.cameray.saturating_sub(distance),
+            Ok(Direction::Right) => self.camerax = self.camerax.saturating_add(distance),
+            Err(err) => return CommandResult::Err(err),
+        }
+        self.clamp_camera();
+        let (rows, cols) = self.view;
+        let (scrolloff, border) = (self.scrolloff, self.zoom.border());
+        let keep = |cursor: usize, camera: isize, visible: usize, len: usize| {
+            let (margin, last) = (margin(scrolloff, visible), camera + visible as isize - 1);
+            let low = if camera > -border { camera + margin } else { 0 };
+            let high = if last < len as isize - 1 + border {
+                last - margin
+            } else {
+                len as isize - 1
+            };
+            (cursor as isize)
+                .min(high)
+                .max(low)
+                .clamp(0, len as isize - 1) as usize
+        };
+        if rows > 0 && cols > 0 {
+            self.cursory = keep(self.cursory, self.cameray, rows, self.map.map.height());
+            self.cursorx = keep(self.cursorx, self.camerax, cols, self.map.map.width());
+        }
         CommandResult::None
     }
 
-    pub(crate) fn select(&mut self, args: &[&str]) -> CommandResult {
-        let map_clone = self.map.clone();
-        match args[0].to_lowercase().as_str() {
-            "all" => {
-                self.map.select =
-                    ((0..self.map.map.len()).cartesian_product(0..self.map.map[0].len())).collect();
-                CommandResult::None
-            }
-            "none" => {
-                self.map.select.clear();
+    pub(crate) fn center(&mut self, _: &[&str]) -> CommandResult {
+        self.cameray = self.cursory as isize - self.view.0 as isize / 2;
+        self.camerax = self.cursorx as isize - self.view.1 as isize / 2;
+        self.clamp_camera();
+        CommandResult::None
+    }
+
+    pub(crate) fn scrolloff(&mut self, args: &[&str]) -> CommandResult {
+        match parse_usize(args[0]) {
+            Ok(scrolloff) => {
+                self.scrolloff = scrolloff;
                 CommandResult::None
             }
+            Err(err) => CommandResult::Err(err),
+        }
+    }
+
+    pub(crate) fn pick(&mut self, _: &[&str]) -> CommandResult {
+        self.brush = Brush::Tile(self.map.map[(self.cursory, self.cursorx)]);
+        CommandResult::None
+    }
+
+    pub(crate) fn select(&mut self, args: &[&str]) -> CommandResult {
+        let (x, y) = (self.map.map.height(), self.map.map.width());
+        let select = match args[0].to_lowercase().as_str() {
+            "all" => Selection::full(x, y),
+            "none" => Selection::new(x, y),
             "invert" => {
-                self.map.select = ((0..self.map.map.len())
-                    .cartesian_product(0..self.map.map[0].len()))
-                .filter(|p| !self.map.select.contains(p))
-                .collect();
-                CommandResult::None
+                let mut select = self.map.select.clone();
+                select.invert();
+                select
             }
             arg => match parse_tile(arg) {
                 Ok(tile) => {
-                    let positions = (0..self.map.map.len())
-                        .cartesian_product(0..self.map.map[0].len())
-                        .filter(|&(i, j)| self.map.map[i][j] == tile);
+                    let positions = Selection::from_positions(
+                        x,
+                        y,
+                        self.map
+                            .map
+                            .positions()
+                            .filter(|&p| self.map.map[p] == tile),
+                    );
                     match self.brush {
-                        Brush::Add => self.map.select.extend(positions),
-                        Brush::Subtract => {
-                            for p in positions {
-                                self.map.select.remove(&p);
-                            }
-                        }
-                        _ => self.map.select = positions.collect(),
+                        Brush::Add => self.map.select.union(&positions),
+                        Brush::Subtract => self.map.select.difference(&positions),
+                        _ => positions,
                     }
-                    CommandResult::None
                 }
                 _ => {
                     return CommandResult::Err(
@@ -485,9 +892,7 @@ impl State {
                 }
             },
         };
-        if self.map.select != map_clone.select {
-            self.push_undo(map_clone);
-        };
+        self.set_selection(select);
         CommandResult::None
     }
 
@@ -496,76 +901,32 @@ impl State {
         F: FnOnce(&[&str]) -> Result<I, String>,
         I: IntoIterator<Item = (usize, usize)>,
     {
-        let (lx, ly) = (self.map.map.len(), self.map.map[0].len());
+        let (lx, ly) = (self.map.map.height(), self.map.map.width());
         let positions = match shape(args) {
             Ok(i) => i,
             Err(err) => return CommandResult::Err(err),
         }
         .into_iter()
         .filter(|(x, y)| in_bounds(lx, ly, *x, *y));
-        let map_clone = self.map.clone();
-        match &self.brush {
-            Brush::Add => {
-                self.map.select.extend(positions);
-                if self.map.select != map_clone.select {
-                    self.push_undo(map_clone);
-                }
-            }
-            Brush::Subtract => {
-                for p in positions {
-                    self.map.select.remove(&p);
-                }
-                if self.map.select != map_clone.select {
-                    self.push_undo(map_clone);
-                }
-            }
-            Brush::Tile(tile) => {
-                if draw_all(&mut self.map.map, positions, *tile) {
-                    self.push_undo(map_clone);
-                }
-            }
-        }
+        self.apply_brush(positions);
         CommandResult::None
     }
 
     pub(crate) fn fuzzy(&mut self, args: &[&str]) -> CommandResult {
-        let map = self.map.map.clone();
-        let cursorx = self.cursorx;
-        let cursory = self.cursory;
-        self.draw_shape(args, |args| {
-            let mut reached = HashSet::new();
-            let mut frontier = HashSet::new();
-            let mut new_frontier = HashSet::new();
-            frontier.insert((cursory, cursorx));
-            let tile = map[cursory][cursorx];
-            let mut i = match args.first() {
-                Some(arg) => parse_usize(arg)? as isize,
-                None => -1,
-            };
-            while !frontier.is_empty() && i != 0 {
-                i -= 1;
-                reached.extend(&frontier);
-                new_frontier.clear();
-                for (i, j) in frontier.clone() {
-                    for (di, dj) in [(-1, 0), (0, -1), (1, 0), (0, 1)] {
-                        let ni = i as isize + di;
-                        let nj = j as isize + dj;
-                        if ni >= 0
-                            && ni < map.len() as isize
-                            && nj >= 0
-                            && nj < map[0].len() as isize
-                            && !reached.contains(&(ni as usize, nj as usize))
-                            && map[ni as usize][nj as usize] == tile
-                        {
-                            new_frontier.insert((ni as usize, nj as usize));
-                        }
-                    }
-                    frontier.clear();
-                    frontier.extend(new_frontier.clone());
-                }
-            }
-            Ok(reached)
-        })
+        let (length, diagonal, loose) = match parse_fuzzy(args) {
+            Ok(options) => options,
+            Err(err) => return CommandResult::Err(err),
+        };
+        let start = (self.cursory, self.cursorx);
+        let map = &self.map.map;
+        let tile = map[start];
+        let fill = if loose {
+            let tile = family(tile);
+            flood(map, start, length, diagonal, |t| family(t) == tile)
+        } else {
+            flood(map, start, length, diagonal, |t| t == tile)
+        };
+        self.draw_shape(args, |_| Ok(fill.iter().collect::<Vec<_>>()))
     }
 
     pub(crate) fn r#box(&mut self, args: &[&str]) -> CommandResult {
@@ -670,55 +1031,147 @@ impl State {
             Ok(x) => x,
             Err(err) => return CommandResult::Err(err),
         };
-        let new_map = Map {
-            map: create(y, x, 0),
-            select: HashSet::new(),
-        };
-        if self.map != new_map {
-            self.push_undo(self.map.clone());
-            self.map = new_map;
-        }
+        let (height, width) = (self.map.map.height(), self.map.map.width());
+        self.set_tiles(self.map.map.positions(), 0);
+        self.set_selection(Selection::new(height, width));
+        self.reframe_map(y, x, 0, 0, 0);
         self.reset_cursor();
         CommandResult::Ok(format!("Created empty {}x{} map.", x, y))
     }
 
-    pub(crate) fn reset_cursor(&mut self) {
-        if !in_bounds(
-            self.map.map[0].len(),
-            self.map.map.len(),
-            self.cursorx,
-            self.cursory,
-        ) {
-            self.cursorx = 0;
-            self.cursory = 0;
+    fn reframe(&mut self, x: usize, y: usize, offx: isize, offy: isize, tile: i32) {
+        self.reframe_map(y, x, offy, offx, tile);
+        self.cursorx = (self.cursorx as isize + offx).clamp(0, x as isize - 1) as usize;
+        self.cursory = (self.cursory as isize + offy).clamp(0, y as isize - 1) as usize;
+        if let Some(clipboard) = &mut self.clipboard {
+            let nx = (clipboard.offsetx as isize + offx).max(0);
+            let ny = (clipboard.offsety as isize + offy).max(0);
+            let (dx, dy) = (
+                nx - clipboard.offsetx as isize,
+                ny - clipboard.offsety as isize,
+            );
+            clipboard.content = clipboard
+                .content
+                .iter()
+                .map(|((i, j), tile)| ((i + dy, j + dx), *tile))
+                .collect();
+            clipboard.offsetx = nx as usize;
+            clipboard.offsety = ny as usize;
         }
     }
 
-    pub(crate) fn undo(&mut self, _: &[&str]) -> CommandResult {
-        match self.undo_stack.pop() {
-            None => CommandResult::Err("Undo stack is empty.".to_owned()),
-            Some(map) => {
-                self.redo_stack.push(self.map.clone());
-                self.map = map;
-                self.reset_cursor();
-                CommandResult::None
-            }
+    pub(crate) fn resize(&mut self, args: &[&str]) -> CommandResult {
+        let x = match parse_usize(args[0]) {
+            Ok(x) => x,
+            Err(err) => return CommandResult::Err(err),
+        };
+        let y = match parse_usize(args[1]) {
+            Ok(y) => y,
+            Err(err) => return CommandResult::Err(err),
+        };
+        if x == 0 || y == 0 {
+            return CommandResult::Err("Maps cannot be empty.".to_owned());
         }
+        let (ax, ay) = match args.get(2).map(|arg| parse_anchor(arg)) {
+            None => (0, 0),
+            Some(Ok(anchor)) => anchor,
+            Some(Err(err)) => return CommandResult::Err(err),
+        };
+        let tile = match args.get(3).map(|arg| parse_tile(arg)) {
+            None => 0,
+            Some(Ok(tile)) => tile,
+            Some(Err(err)) => return CommandResult::Err(err),
+        };
+        let offset = |anchor: usize, old: usize, new: usize| match anchor {
+            0 => 0,
+            1 => (new as isize - old as isize) / 2,
+            _ => new as isize - old as isize,
+        };
+        self.reframe(
+            x,
+            y,
+            offset(ax, self.map.map.width(), x),
+            offset(ay, self.map.map.height(), y),
+            tile,
+        );
+        CommandResult::Ok(format!("Resized map to {}x{}.", x, y))
     }
 
-    pub(crate) fn redo(&mut self, _: &[&str]) -> CommandResult {
-        match self.redo_stack.pop() {
-            None => CommandResult::Err("Redo stack is empty".to_owned()),
-            Some(map) => {
-                self.undo_stack.push(self.map.clone());
-                self.map = map;
-                self.reset_cursor();
-                CommandResult::None
+    pub(crate) fn crop(&mut self, _: &[&str]) -> CommandResult {
+        let (Some(i0), Some(i1), Some(j0), Some(j1)) = (
+            self.map.select.iter().map(|p| p.0).min(),
+            self.map.select.iter().map(|p| p.0).max(),
+            self.map.select.iter().map(|p| p.1).min(),
+            self.map.select.iter().map(|p| p.1).max(),
+        ) else {
+            return CommandResult::Err("Selection is empty.".to_owned());
+        };
+        let (x, y) = (j1 - j0 + 1, i1 - i0 + 1);
+        self.reframe(x, y, -(j0 as isize), -(i0 as isize), 0);
+        CommandResult::Ok(format!("Cropped map to {}x{}.", x, y))
+    }
+
+    pub(crate) fn pad(&mut self, args: &[&str]) -> CommandResult {
+        let mut sides = [0; 4];
+        for (side, arg) in sides.iter_mut().zip(args) {
+            *side = match parse_usize(arg) {
+                Ok(side) => side,
+                Err(err) => return CommandResult::Err(err),
+            };
+        }
+        let [left, top, right, bottom] = sides;
+        let tile = match parse_tile(args[4]) {
+            Ok(tile) => tile,
+            Err(err) => return CommandResult::Err(err),
+        };
+        let (x, y) = (
+            self.map.map.width() + left + right,
+            self.map.map.height() + top + bottom,
+        );
+        self.reframe(x, y, left as isize, top as isize, tile);
+        CommandResult::Ok(format!("Padded map to {}x{}.", x, y))
+    }
+
+    pub(crate) fn map(&mut self, args: &[&str]) -> CommandResult {
+        let (transform, message) = match (
+            args[0].to_lowercase().as_str(),
+            args.get(1).map(|arg| arg.to_lowercase()).as_deref(),
+        ) {
+            ("rotate", Some("cw")) => (Transform::RotateClockwise, "Rotated the map clockwise."),
+            ("rotate", Some("ccw")) => (
+                Transform::RotateAnticlockwise,
+                "Rotated the map anticlockwise.",
+            ),
+            ("rotate", Some("180")) => (Transform::RotateHalf, "Rotated the map by 180 degrees."),
+            ("flip", Some("h")) => (Transform::FlipHorizontal, "Flipped the map horizontally."),
+            ("flip", Some("v")) => (Transform::FlipVertical, "Flipped the map vertically."),
+            ("transpose", None) => (Transform::Transpose, "Transposed the map."),
+            _ => {
+                return CommandResult::Err(
+                    "Invalid options, the only options are rotate cw|ccw|180, flip h|v and transpose."
+                        .to_owned(),
+                );
             }
+        };
+        let (x, y) = (self.map.map.height(), self.map.map.width());
+        self.transform_all(transform);
+        (self.cursory, self.cursorx) = transform.apply(x, y, self.cursory, self.cursorx);
+        CommandResult::Ok(message.to_owned())
+    }
+
+    pub(crate) fn reset_cursor(&mut self) {
+        if !in_bounds(
+            self.map.map.width(),
+            self.map.map.height(),
+            self.cursorx,
+            self.cursory,
+        ) {
+            self.cursorx = 0;
+            self.cursory = 0;
         }
     }
 
-    pub(crate) fn parse_command(&mut self, text: &str) -> CommandResult {
+    pub fn parse_command(&mut self, text: &str) -> CommandResult {
         if let Some((name, args)) = text
             .split(" ")
             .filter(|s| !s.is_empty())
@@ -730,18 +1183,31 @@ impl State {
                 .find(|c| c.name == *name || c.aliases.contains(name))
             {
                 None => CommandResult::Err(format!("Command {} not found.", name)),
+                Some(command) if self.floating.is_some() && LIFTED.contains(&command.name) => {
+                    CommandResult::Err(format!(
+                        "Cannot {} while tiles are lifted (use :drop or :cancel first).",
+                        command.name
+                    ))
+                }
                 Some(command) => {
                     if args.len() >= command.argsmin && args.len() <= command.argsmax {
-                        (command.function)(self, args)
+                        let result = (command.function)(self, args);
+                        if REPEATABLE.contains(&command.name)
+                            && !matches!(result, CommandResult::Err(_))
+                        {
+                            self.repeat = Some(Repeat {
+                                name: command.name,
+                                args: args.iter().map(|arg| arg.to_string()).collect(),
+                                cursor: (self.cursorx, self.cursory),
+                                brush: self.brush,
+                            });
+                        }
+                        result
                     } else {
                         CommandResult::Err(format!(
                             "Incorrect number of arguments for {}: expected {}, found {}.",
                             command.name,
-                            if command.argsmin == command.argsmax {
-                                command.argsmin.to_string()
-                            } else {
-                                format!("{}-{}", command.argsmin, command.argsmax)
-                            },
+                            command.arguments(),
                             args.len()
                         ))
                     }
@@ -754,7 +1220,7 @@ impl State {
 
     pub(crate) fn info_bar(&self) -> String {
         format!(
-            "Path: {}{}, Pen: {}, Brush: {}, Cursor: ({},{}), Argument: {}",
+            "Path: {}{}, Pen: {}, Brush: {}, Size: {} {}, Zoom: {}, Cursor: ({},{}), Argument: {}{}",
             self.path.clone().unwrap_or("[-]".to_owned()),
             if self.modified() { "(*)" } else { "" },
             match self.pen {
@@ -766,12 +1232,28 @@ impl State {
                 Brush::Subtract => "subtract",
                 Brush::Tile(tile) => TILES.tiles.iter().find(|t| t.0 as i32 == tile).unwrap().1,
             },
+            self.size,
+            match self.footprint {
+                Footprint::Square => "square",
+                Footprint::Circle => "circle",
+                Footprint::Diamond => "diamond",
+            },
+            match self.zoom {
+                Zoom::Wide => "2x1".to_owned(),
+                Zoom::Square => "1x1".to_owned(),
+                Zoom::Blocks(1) => "half".to_owned(),
+                Zoom::Blocks(n) => format!("1/{}", n),
+            },
             self.cursorx,
             self.cursory,
             if self.argument > 0 {
                 self.argument.to_string()
             } else {
                 "".to_owned()
+            },
+            match &self.recording {
+                Some((register, _)) => format!(", Recording: @{}", register),
+                None => "".to_owned(),
             }
         )
     }
@@ -782,7 +1264,7 @@ impl State {
                 .map
                 .select
                 .iter()
-                .map(|(i, j)| ((*i as isize, *j as isize), self.map.map[*i][*j]))
+                .map(|(i, j)| ((i as isize, j as isize), self.map.map[(i, j)]))
                 .collect(),
             offsetx: self.cursorx,
             offsety: self.cursory,
@@ -793,10 +1275,41 @@ impl State {
         ))
     }
 
-    pub(crate) fn paste(&mut self, _: &[&str]) -> CommandResult {
-        let ly = self.map.map[0].len();
-        let lx = self.map.map.len();
-        let map_clone = self.map.clone();
+    pub(crate) fn paste(&mut self, args: &[&str]) -> CommandResult {
+        let count = match parse_count(args, 0) {
+            Ok(count) => count,
+            Err(err) => return CommandResult::Err(err),
+        };
+        let Some(clipboard) = &self.clipboard else {
+            return CommandResult::Err("Clipboard is empty".to_owned());
+        };
+        let extent = |f: fn(&(isize, isize)) -> isize| {
+            let (min, max) = clipboard
+                .content
+                .keys()
+                .map(f)
+                .minmax()
+                .into_option()
+                .unwrap_or((0, 0));
+            (max - min + 1) as usize
+        };
+        let distance = match self.direction {
+            Direction::Left | Direction::Right => extent(|p| p.1),
+            Direction::Down | Direction::Up => extent(|p| p.0),
+        };
+        self.paste_once();
+        for _ in 1..count {
+            if !self.step(self.direction, distance) {
+                break;
+            }
+            self.paste_once();
+        }
+        CommandResult::None
+    }
+
+    fn paste_once(&mut self) {
+        let ly = self.map.map.width();
+        let lx = self.map.map.height();
         if let Some(clipboard) = &self.clipboard {
             for (i, j, tile) in clipboard
                 .content
@@ -805,19 +1318,14 @@ impl State {
                     (
                         (*i + self.cursory as isize - (clipboard.offsety as isize)) as usize,
                         (*j + self.cursorx as isize - (clipboard.offsetx as isize)) as usize,
-                        tile,
+                        *tile,
                     )
                 })
                 .filter(|(i, j, _)| in_bounds(lx, ly, *i, *j))
+                .collect::<Vec<_>>()
             {
-                self.map.map[i][j] = *tile;
+                self.set_tile(i, j, tile);
             }
-            if self.map.map != map_clone.map {
-                self.push_undo(map_clone);
-            }
-            CommandResult::None
-        } else {
-            CommandResult::Err("Clipboard is empty".to_owned())
         }
     }
 
@@ -882,118 +1390,1315 @@ impl State {
         }
     }
 
-    fn move_with(&mut self, direction: Direction) -> CommandResult {
-        self.move_cursor(direction, self.argument.max(1));
-        self.argument = 0;
-        CommandResult::None
-    }
-    pub(crate) fn receive_key_closed(&mut self, code: KeyCode) {
-        match &code {
-            KeyCode::Char(':') => {
-                self.bar = Bar::Input(Input::empty());
-            }
-            KeyCode::Char('h') | KeyCode::Left => {
-                self.move_with(Direction::Left);
+    pub(crate) fn tile_at(&self, i: usize, j: usize) -> i32 {
+        if let Some(floating) = &self.floating {
+            let (si, sj) = (i as isize - floating.offsety, j as isize - floating.offsetx);
+            if si >= 0
+                && sj >= 0
+                && let Some(tile) = floating.content.get(&(si as usize, sj as usize))
+            {
+                return *tile;
+            } else if floating.content.contains_key(&(i, j)) {
+                return self.background;
             }
-            KeyCode::Char('j') | KeyCode::Down => {
-                self.move_with(Direction::Down);
+        }
+        self.map.map[(i, j)]
+    }
+
+    pub(crate) fn selected(&self, i: usize, j: usize) -> bool {
+        match &self.floating {
+            Some(floating) => {
+                let (si, sj) = (i as isize - floating.offsety, j as isize - floating.offsetx);
+                si >= 0 && sj >= 0 && floating.content.contains_key(&(si as usize, sj as usize))
             }
-            KeyCode::Char('k') | KeyCode::Up => {
-                self.move_with(Direction::Up);
+            None => self.map.select.contains(&(i, j)),
+        }
+    }
+
+    fn relocate<F>(&mut self, content: &HashMap<(usize, usize), i32>, target: F) -> usize
+    where
+        F: Fn(usize, usize) -> (isize, isize),
+    {
+        let (lx, ly) = (self.map.map.height(), self.map.map.width());
+        let mut dropped = 0;
+        let mut select = Selection::new(lx, ly);
+        self.set_tiles(
+            content
+                .keys()
+                .copied()
+                .filter(|&(i, j)| in_bounds(lx, ly, i, j)),
+            self.background,
+        );
+        for (&(i, j), tile) in content {
+            let (ni, nj) = target(i, j);
+            if ni >= 0 && nj >= 0 && in_bounds(lx, ly, ni as usize, nj as usize) {
+                self.set_tile(ni as usize, nj as usize, *tile);
+                select.insert((ni as usize, nj as usize));
+            } else {
+                dropped += 1;
             }
-            KeyCode::Char('l') | KeyCode::Right => {
-                self.move_with(Direction::Right);
+        }
+        self.set_selection(select);
+        dropped
+    }
+
+    pub(crate) fn selection(&mut self, args: &[&str]) -> CommandResult {
+        let (Some(i0), Some(i1), Some(j0), Some(j1)) = (
+            self.map.select.iter().map(|p| p.0).min(),
+            self.map.select.iter().map(|p| p.0).max(),
+            self.map.select.iter().map(|p| p.1).min(),
+            self.map.select.iter().map(|p| p.1).max(),
+        ) else {
+            return CommandResult::Err("Selection is empty.".to_owned());
+        };
+        let (ci, cj) = match args.get(2).map(|arg| arg.to_lowercase()).as_deref() {
+            None | Some("center") => ((i0 + i1) as isize, (j0 + j1) as isize),
+            Some("cursor") => (2 * self.cursory as isize, 2 * self.cursorx as isize),
+            Some(arg) => {
+                return CommandResult::Err(format!(
+                    "Invalid pivot {}, options are center, cursor.",
+                    arg
+                ));
             }
-            KeyCode::Char('H') => {
-                self.edge(&["left"]);
+        };
+        let transform: fn(isize, isize) -> (isize, isize) = match (
+            args[0].to_lowercase().as_str(),
+            args.get(1).map(|arg| arg.to_lowercase()).as_deref(),
+        ) {
+            ("rotate", Some("cw")) => |di, dj| (dj, -di),
+            ("rotate", Some("ccw")) => |di, dj| (-dj, di),
+            ("flip", Some("h")) => |di, dj| (di, -dj),
+            ("flip", Some("v")) => |di, dj| (-di, dj),
+            _ => {
+                return CommandResult::Err(
+                    "Invalid options, the only options are rotate cw|ccw and flip h|v.".to_owned(),
+                );
             }
-            KeyCode::Char('J') => {
-                self.edge(&["down"]);
+        };
+        let content = self
+            .map
+            .select
+            .iter()
+            .map(|(i, j)| ((i, j), self.map.map[(i, j)]))
+            .collect();
+        let dropped = self.relocate(&content, |i, j| {
+            let (di, dj) = transform(2 * i as isize - ci, 2 * j as isize - cj);
+            ((ci + di).div_euclid(2), (cj + dj).div_euclid(2))
+        });
+        if dropped > 0 {
+            CommandResult::Ok(format!(
+                "Warning: {} tiles were dropped outside the map.",
+                dropped
+            ))
+        } else {
+            CommandResult::None
+        }
+    }
+
+    pub(crate) fn zoom(&mut self, args: &[&str]) -> CommandResult {
+        self.zoom = match args[0].to_lowercase().as_str() {
+            "in" => self.zoom.zoom_in(),
+            "out" => self.zoom.zoom_out(),
+            "wide" => Zoom::Wide,
+            "square" => Zoom::Square,
+            "half" => Zoom::Blocks(1),
+            arg => match parse_usize(arg) {
+                Ok(n) if (1..=MAX_ZOOM).contains(&n) => Zoom::Blocks(n),
+                _ => {
+                    return CommandResult::Err(format!(
+                        "Invalid zoom {}, options are in, out, wide, square, half or a factor from 1 to {}.",
+                        arg, MAX_ZOOM
+                    ));
+                }
+            },
+        };
+        CommandResult::None
+    }
+
+    pub(crate) fn minimap(&mut self, args: &[&str]) -> CommandResult {
+        self.minimap = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+            None => !self.minimap,
+            Some("on") => true,
+            Some("off") => false,
+            Some(arg) => {
+                return CommandResult::Err(format!(
+                    "Invalid argument {}, options are on and off.",
+                    arg
+                ));
             }
-            KeyCode::Char('K') => {
-                self.edge(&["up"]);
+        };
+        CommandResult::None
+    }
+
+    pub(crate) fn glyphs(&mut self, args: &[&str]) -> CommandResult {
+        self.glyphs = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+            None => !self.glyphs,
+            Some("on") => true,
+            Some("off") => false,
+            Some(arg) => {
+                return CommandResult::Err(format!(
+                    "Invalid argument {}, options are on and off.",
+                    arg
+                ));
             }
-            KeyCode::Char('L') => {
-                self.edge(&["right"]);
+        };
+        CommandResult::None
+    }
+
+    pub(crate) fn rulers(&mut self, args: &[&str]) -> CommandResult {
+        self.rulers = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+            None => !self.rulers,
+            Some("on") => true,
+            Some("off") => false,
+            Some(arg) => {
+                return CommandResult::Err(format!(
+                    "Invalid argument {}, options are on and off.",
+                    arg
+                ));
             }
-            KeyCode::Char('d') => {
-                self.dot(&[]);
+        };
+        CommandResult::None
+    }
+
+    pub(crate) fn grid(&mut self, args: &[&str]) -> CommandResult {
+        match parse_usize(args[0]) {
+            Ok(grid) => {
+                self.grid = grid;
+                CommandResult::None
             }
-            KeyCode::Char('a') => {
-                self.brush(&["add"]);
+            Err(err) => CommandResult::Err(err),
+        }
+    }
+
+    pub(crate) fn palette(&mut self, args: &[&str]) -> CommandResult {
+        let show = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+            None => self.palette.is_none(),
+            Some("on") | Some("focus") => true,
+            Some("off") => false,
+            Some(arg) => {
+                return CommandResult::Err(format!(
+                    "Invalid argument {}, options are on, off and focus.",
+                    arg
+                ));
             }
-            KeyCode::Char('s') => {
-                self.brush(&["subtract"]);
+        };
+        self.palette = match self.palette.take() {
+            Some(palette) if show => Some(palette),
+            None if show => Some(Palette::new(match self.brush {
+                Brush::Tile(tile) => Some(tile),
+                _ => None,
+            })),
+            _ => None,
+        };
+        if let Some(palette) = &mut self.palette {
+            palette.focus = args
+                .first()
+                .is_some_and(|arg| arg.eq_ignore_ascii_case("focus"));
+        }
+        CommandResult::None
+    }
+
+    pub(crate) fn colors(&mut self, args: &[&str]) -> CommandResult {
+        match args.first() {
+            None => CommandResult::Ok(format!("Color mode: {}.", self.colors.name())),
+            Some(arg) => match ColorMode::parse(arg) {
+                Ok(colors) => {
+                    self.colors = colors;
+                    CommandResult::None
+                }
+                Err(err) => CommandResult::Err(err),
+            },
+        }
+    }
+
+    pub(crate) fn background(&mut self, args: &[&str]) -> CommandResult {
+        match parse_tile(args[0]) {
+            Ok(tile) => {
+                self.background = tile;
+                CommandResult::None
             }
-            KeyCode::Char('i') => {
-                self.pen(&["down"]);
+            Err(err) => CommandResult::Err(err),
+        }
+    }
+
+    pub(crate) fn lift(&mut self, _: &[&str]) -> CommandResult {
+        if self.floating.is_some() {
+            CommandResult::Err("Already lifted (use :drop or :cancel first).".to_owned())
+        } else if self.map.select.is_empty() {
+            CommandResult::Err("Selection is empty.".to_owned())
+        } else {
+            self.floating = Some(Floating {
+                content: self
+                    .map
+                    .select
+                    .iter()
+                    .map(|(i, j)| ((i, j), self.map.map[(i, j)]))
+                    .collect(),
+                offsetx: 0,
+                offsety: 0,
+            });
+            CommandResult::Ok(format!("Lifted {} tiles.", self.map.select.len()))
+        }
+    }
+
+    pub(crate) fn nudge(&mut self, args: &[&str]) -> CommandResult {
+        let distance = match args.get(1) {
+            None => 1,
+            Some(arg) => match parse_usize(arg) {
+                Ok(distance) => distance.min(isize::MAX as usize) as isize,
+                Err(err) => return CommandResult::Err(err),
+            },
+        };
+        let direction = match parse_direction(args[0]) {
+            Ok(direction) => direction,
+            Err(err) => return CommandResult::Err(err),
+        };
+        let Some(floating) = &mut self.floating else {
+            return CommandResult::Err("Nothing is lifted (use :lift first).".to_owned());
+        };
+        let (dx, dy) = match direction {
+            Direction::Left => (-distance, 0),
+            Direction::Down => (0, distance),
+            Direction::Up => (0, -distance),
+            Direction::Right => (distance, 0),
+        };
+        let (x, y) = (self.map.map.height(), self.map.map.width());
+        let bounds = |f: fn(&(usize, usize)) -> usize, length: usize| {
+            let (min, max) = floating
+                .content
+                .keys()
+                .map(f)
+                .minmax()
+                .into_option()
+                .unwrap();
+            (-(min as isize), (length - 1 - max) as isize)
+        };
+        let (left, right) = bounds(|p| p.1, y);
+        let (top, bottom) = bounds(|p| p.0, x);
+        let offsetx = floating.offsetx.saturating_add(dx).clamp(left, right);
+        let offsety = floating.offsety.saturating_add(dy).clamp(top, bottom);
+        let (dx, dy) = (offsetx - floating.offsetx, offsety - floating.offsety);
+        floating.offsetx = offsetx;
+        floating.offsety = offsety;
+        self.cursorx =
+            (self.cursorx as isize + dx).clamp(0, self.map.map.width() as isize - 1) as usize;
+        self.cursory =
+            (self.cursory as isize + dy).clamp(0, self.map.map.height() as isize - 1) as usize;
+        CommandResult::None
+    }
+
+    pub(crate) fn drop(&mut self, _: &[&str]) -> CommandResult {
+        match self.floating.take() {
+            None => CommandResult::Err("Nothing is lifted (use :lift first).".to_owned()),
+            Some(floating) => {
+                let dropped = self.relocate(&floating.content, |i, j| {
+                    (i as isize + floating.offsety, j as isize + floating.offsetx)
+                });
+                if dropped > 0 {
+                    CommandResult::Ok(format!(
+                        "Warning: {} tiles were dropped outside the map.",
+                        dropped
+                    ))
+                } else {
+                    CommandResult::None
+                }
             }
-            KeyCode::Char('I') => {
-                self.pen(&["up"]);
+        }
+    }
+
+    pub(crate) fn cancel(&mut self, _: &[&str]) -> CommandResult {
+        match self.floating.take() {
+            None => CommandResult::Err("Nothing is lifted.".to_owned()),
+            Some(_) => CommandResult::None,
+        }
+    }
+
+    fn receive_key_floating(&mut self, code: KeyCode) {
+        let direction = match &code {
+            KeyCode::Char('h') | KeyCode::Left => "left",
+            KeyCode::Char('j') | KeyCode::Down => "down",
+            KeyCode::Char('k') | KeyCode::Up => "up",
+            KeyCode::Char('l') | KeyCode::Right => "right",
+            KeyCode::Char(':') => {
+                self.bar = Bar::Input(Input::empty());
+                return;
             }
-            KeyCode::Char('A') => {
-                self.select(&["all"]);
+            KeyCode::Char('m') | KeyCode::Enter => {
+                if let CommandResult::Ok(message) = self.drop(&[]) {
+                    self.bar = Bar::Ok(message);
+                }
+                return;
             }
-            KeyCode::Char('S') => {
-                self.select(&["none"]);
+            KeyCode::Esc => {
+                if self.argument > 0 {
+                    self.argument = 0;
+                } else {
+                    self.cancel(&[]);
+                }
+                return;
             }
-            KeyCode::Char('F') => {
-                self.select(&["invert"]);
+            KeyCode::Char(c) => {
+                if let Some(i) = c.to_digit(10) {
+                    self.append_argument(i as u8)
+                };
+                return;
             }
-            KeyCode::Esc => {
-                self.argument = 0;
+            _ => return,
+        };
+        self.nudge(&[direction, &self.argument.max(1).to_string()]);
+        self.argument = 0;
+    }
+
+    fn run_keybind(&mut self, keybind: &Keybind) {
+        let command = if keybind.count {
+            format!("{} {}", keybind.command, self.argument.max(1))
+        } else {
+            keybind.command.to_owned()
+        };
+        self.argument = 0;
+        match self.parse_command(&command) {
+            CommandResult::Err(err) => self.bar = Bar::Err(err),
+            CommandResult::Ok(message) => self.bar = Bar::Ok(message),
+            CommandResult::None => (),
+        }
+    }
+
+    pub(crate) fn receive_key_closed(&mut self, code: KeyCode, name: String) {
+        if let [prefix] = self.pending.as_slice()
+            && (prefix == "q" || prefix == "@")
+        {
+            let record = prefix == "q";
+            self.pending.clear();
+            match parse_register(&name) {
+                Ok(register) if record => self.recording = Some((register, Vec::new())),
+                Ok(register) => self.play(register),
+                Err(_) => self.argument = 0,
             }
-            KeyCode::Char('f') => {
-                self.bucket(&[]);
+            return;
+        }
+        match code {
+            KeyCode::Char('q') if self.recording.is_some() => {
+                if let Some((register, mut keys)) = self.recording.take() {
+                    keys.pop();
+                    self.macros.insert(register, keys);
+                }
+                return;
             }
-            KeyCode::Char('p') => {
-                self.pick(&[]);
+            KeyCode::Char('q') | KeyCode::Char('@') if self.pending.is_empty() => {
+                self.pending.push(name);
+                return;
             }
-            KeyCode::Char('u') => {
-                self.undo(&[]);
+            _ => (),
+        }
+        if self.floating.is_some() {
+            self.receive_key_floating(code);
+            return;
+        }
+        if self.pending.is_empty() {
+            match code {
+                KeyCode::Char(':') => {
+                    self.bar = Bar::Input(Input::empty());
+                    return;
+                }
+                KeyCode::Esc => {
+                    self.argument = 0;
+                    return;
+                }
+                KeyCode::Char(c) if c.is_ascii_digit() && name.len() == 1 => {
+                    self.append_argument(c.to_digit(10).unwrap() as u8);
+                    return;
+                }
+                _ => (),
             }
-            KeyCode::Char('U') => {
-                self.redo(&[]);
+        }
+        self.pending.push(name);
+        let sequence = self.pending.join(" ");
+        if let Some(keybind) = KEYBINDS
+            .iter()
+            .find(|k| k.keys.contains(&sequence.as_str()))
+        {
+            self.pending.clear();
+            self.run_keybind(keybind);
+        } else if !KEYBINDS
+            .iter()
+            .flat_map(|k| k.keys)
+            .any(|keys| keys.starts_with(&(sequence.clone() + " ")))
+        {
+            self.pending.clear();
+            self.argument = 0;
+        }
+    }
+
+    pub(crate) fn repeat(&mut self, args: &[&str]) -> CommandResult {
+        let count = match parse_count(args, 0) {
+            Ok(count) => count,
+            Err(err) => return CommandResult::Err(err),
+        };
+        let Some(repeat) = self.repeat.take() else {
+            return CommandResult::Err("Nothing to repeat.".to_owned());
+        };
+        let brush = replace(&mut self.brush, repeat.brush);
+        self.begin();
+        let result = self.run_repeat(&repeat, count);
+        self.end();
+        self.brush = brush;
+        self.repeat = Some(repeat);
+        result
+    }
+
+    fn run_repeat(&mut self, repeat: &Repeat, count: usize) -> CommandResult {
+        let Some(command) = COMMANDS.iter().find(|c| c.name == repeat.name) else {
+            return CommandResult::None;
+        };
+        if matches!(repeat.name, "dot" | "paste") {
+            return (command.function)(self, &[&count.to_string()]);
+        }
+        let mut result = CommandResult::None;
+        for n in 0..count {
+            let mut args = repeat.args.clone();
+            if matches!(repeat.name, "box" | "ellipse") {
+                let mut corners = [0; 4];
+                for (corner, arg) in corners.iter_mut().zip(&args) {
+                    match parse_usize(arg) {
+                        Ok(v) => *corner = v,
+                        Err(err) => return CommandResult::Err(err),
+                    }
+                }
+                let distance = match self.direction {
+                    Direction::Left | Direction::Right => corners[0].abs_diff(corners[2]) + 1,
+                    Direction::Up | Direction::Down => corners[1].abs_diff(corners[3]) + 1,
+                };
+                if n > 0 && !self.step(self.direction, distance) {
+                    break;
+                }
+                let shift = (
+                    self.cursorx as isize - repeat.cursor.0 as isize,
+                    self.cursory as isize - repeat.cursor.1 as isize,
+                );
+                for (n, (arg, corner)) in args.iter_mut().zip(corners).enumerate() {
+                    let shift = if n % 2 == 0 { shift.0 } else { shift.1 };
+                    match corner as isize + shift {
+                        v if v >= 0 => *arg = v.to_string(),
+                        _ => {
+                            return CommandResult::Err("The shape would leave the map.".to_owned());
+                        }
+                    }
+                }
+            } else if n > 0 && !self.step(self.direction, 1) {
+                break;
             }
-            KeyCode::Char('o') => {
-                self.copy(&[]);
+            let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+            result = (command.function)(self, &args);
+            if let CommandResult::Err(_) = result {
+                break;
             }
-            KeyCode::Char('O') => {
-                self.paste(&[]);
+        }
+        result
+    }
+
+    pub(crate) fn r#macro(&mut self, args: &[&str]) -> CommandResult {
+        if args[0] == "save" {
+            return match args.get(1).map(|arg| parse_register(arg)) {
+                Some(Ok(register)) => self.save_macro(register),
+                Some(Err(err)) => CommandResult::Err(err),
+                None => CommandResult::Err("Missing the register to save.".to_owned()),
+            };
+        }
+        let register = match parse_register(args[0]) {
+            Ok(register) => register,
+            Err(err) => return CommandResult::Err(err),
+        };
+        match args.get(1) {
+            Some(keys) => match parse_keys(keys) {
+                Ok(keys) => {
+                    self.macros.insert(register, keys);
+                    CommandResult::None
+                }
+                Err(err) => CommandResult::Err(err),
+            },
+            None => match self.macros.get(&register) {
+                Some(keys) => CommandResult::Ok(format!("@{}: {}", register, format_keys(keys))),
+                None => CommandResult::Err(format!("Register {} is empty.", register)),
+            },
+        }
+    }
+
+    fn save_macro(&mut self, register: char) -> CommandResult {
+        let Some(keys) = self.macros.get(&register).filter(|keys| !keys.is_empty()) else {
+            return CommandResult::Err(format!("Register {} is empty.", register));
+        };
+        let Some(path) = config_path() else {
+            return CommandResult::Err("Could not find the config directory.".to_owned());
+        };
+        let line = format!("macro {} ", register);
+        let config = read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|l| !l.trim().starts_with(&line))
+            .map(str::to_owned)
+            .chain([format!("{}{}", line, format_keys(keys))])
+            .join("\n");
+        match path
+            .parent()
+            .map_or(Ok(()), create_dir_all)
+            .and_then(|_| write(&path, config + "\n"))
+        {
+            Ok(_) => CommandResult::Ok(format!("Saved @{} to {}.", register, path.display())),
+            Err(_) => CommandResult::Err(format!("Could not write to {}.", path.display())),
+        }
+    }
+
+    fn play(&mut self, register: char) {
+        let count = self.argument.max(1);
+        self.argument = 0;
+        let Some(keys) = self.macros.get(&register).cloned() else {
+            self.bar = Bar::Err(format!("Register {} is empty.", register));
+            return;
+        };
+        if self.playing >= MAX_MACRO_DEPTH {
+            self.bar = Bar::Err("Macros are nested too deeply.".to_owned());
+            return;
+        }
+        if self.playing == 0 {
+            self.played = 0;
+        }
+        self.playing += 1;
+        self.begin();
+        'play: for _ in 0..count {
+            for key in &keys {
+                // Macros playing themselves can multiply the keys without nesting any deeper.
+                if self.played >= MAX_MACRO_KEYS {
+                    self.bar = Bar::Err(format!(
+                        "Macros stopped after playing {} keys.",
+                        MAX_MACRO_KEYS
+                    ));
+                    break 'play;
+                }
+                self.played += 1;
+                self.receive_key(*key);
             }
-            KeyCode::Char(c) => {
-                if let Some(i) = c.to_digit(10) {
-                    self.append_argument(i as u8)
+        }
+        self.end();
+        self.playing -= 1;
+    }
+
+    pub(crate) fn command_history(&mut self, _args: &[&str]) -> CommandResult {
+        let entries = &self.commands.entries;
+        if entries.is_empty() {
+            return CommandResult::Ok("No commands entered yet.".to_owned());
+        }
+        let lines = entries
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(n, entry)| format!("{:>4}  {}", n + 1, entry))
+            .collect();
+        self.popup = Some(Popup::new("History", lines));
+        CommandResult::None
+    }
+
+    pub(crate) fn help(&mut self, args: &[&str]) -> CommandResult {
+        let keys = |name: &str| {
+            KEYBINDS
+                .iter()
+                .filter(|k| k.command.split(' ').next() == Some(name))
+                .flat_map(|k| k.keys.iter().map(|keys| display_keys(keys)))
+                .collect::<Vec<_>>()
+        };
+        let lines = match args.first() {
+            Some(arg) => {
+                let Some(command) = COMMANDS
+                    .iter()
+                    .find(|c| c.name == *arg || c.aliases.contains(arg))
+                else {
+                    return CommandResult::Err(format!("Command {} not found.", arg));
                 };
+                let mut lines = vec![
+                    format!("{} {}", command.name, command.usage),
+                    String::new(),
+                    command.description.to_owned(),
+                    String::new(),
+                    format!("Arguments: {}", command.arguments()),
+                ];
+                if !command.aliases.is_empty() {
+                    lines.push(format!("Aliases: {}", command.aliases.join(", ")));
+                }
+                let keys = keys(command.name);
+                if !keys.is_empty() {
+                    lines.push(format!("Keys: {}", keys.join(", ")));
+                }
+                lines
+            }
+            None => {
+                let mut lines = vec![
+                    "Commands are entered after :, optional arguments are marked with ?."
+                        .to_owned(),
+                    "Use :help <command> for details.".to_owned(),
+                    String::new(),
+                ];
+                for command in COMMANDS.iter() {
+                    let name = if command.aliases.is_empty() {
+                        command.name.to_owned()
+                    } else {
+                        format!("{} ({})", command.name, command.aliases.join(", "))
+                    };
+                    lines.push(format!("{:<20} {}", name, command.usage));
+                    lines.push(format!("    {}", command.description));
+                }
+                lines.push(String::new());
+                lines.push(
+                    "Keys, typing a number first passes it as the count to those marked with #:"
+                        .to_owned(),
+                );
+                lines.push(String::new());
+                for keybind in KEYBINDS.iter() {
+                    lines.push(format!(
+                        "{:<20} {}{}",
+                        keybind
+                            .keys
+                            .iter()
+                            .map(|keys| display_keys(keys))
+                            .join(", "),
+                        keybind.command,
+                        if keybind.count { " #" } else { "" }
+                    ));
+                }
+                lines
             }
-            _ => {}
         };
+        self.popup = Some(Popup::new("Help", lines));
+        CommandResult::None
+    }
+}
+
+fn display_keys(keys: &str) -> String {
+    keys.split(' ')
+        .map(|key| {
+            if key.chars().count() == 1 {
+                key.to_owned()
+            } else {
+                format!("<{}>", key)
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn completions(text: &str) -> (usize, Vec<String>) {
+    let start = text.rfind(' ').map_or(0, |i| i + 1);
+    let word = &text[start..];
+    let words = text[..start]
+        .split(' ')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    let options = match words.split_first() {
+        None => COMMANDS
+            .iter()
+            .flat_map(|c| std::iter::once(&c.name).chain(c.aliases))
+            .map(|name| name.to_string())
+            .collect(),
+        Some((name, args)) => {
+            let Some(command) = COMMANDS
+                .iter()
+                .find(|c| c.name == *name || c.aliases.contains(name))
+            else {
+                return (start, Vec::new());
+            };
+            let usage = command
+                .completion
+                .split(' ')
+                .skip(args.len())
+                .collect::<Vec<_>>();
+            // Optional arguments may be left out, so the ones after them are offered as well.
+            let end = usage
+                .iter()
+                .position(|option| !option.ends_with('?'))
+                .map_or(usage.len(), |i| i + 1);
+            usage[..end]
+                .iter()
+                .flat_map(|option| option.trim_end_matches('?').split('|'))
+                .flat_map(|option| match option {
+                    "<path>" => complete_path(word),
+                    "<tile>" => TILES.tiles.iter().map(|t| t.1.to_owned()).collect(),
+                    "<command>" => COMMANDS.iter().map(|c| c.name.to_owned()).collect(),
+                    "<direction>" => ["left", "down", "up", "right"].map(str::to_owned).to_vec(),
+                    "<anchor>" => ["nw", "n", "ne", "w", "c", "e", "sw", "s", "se"]
+                        .map(str::to_owned)
+                        .to_vec(),
+                    option if option.starts_with('<') => Vec::new(),
+                    option => vec![option.to_owned()],
+                })
+                .collect::<Vec<_>>()
+        }
+    };
+    let lower = word.to_lowercase();
+    let candidates = options
+        .into_iter()
+        .filter(|option| option.to_lowercase().starts_with(&lower))
+        .unique()
+        .collect();
+    (start, candidates)
+}
+
+fn complete_path(path: &str) -> Vec<String> {
+    let (dir, prefix) = path.rfind('/').map_or(("", path), |i| path.split_at(i + 1));
+    let Ok(entries) = read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, slash))
+        })
+        .sorted()
+        .collect()
+}
+
+struct Keybind {
+    keys: &'static [&'static str],
+    command: &'static str,
+    count: bool,
+}
+
+impl Keybind {
+    const fn new(keys: &'static [&'static str], command: &'static str, count: bool) -> Self {
+        Keybind {
+            keys,
+            command,
+            count,
+        }
     }
 }
 
-const COMMANDS: [Command; 24] = [
-    Command::new("open", &["o"], 1, 1, State::open),
-    Command::new("open!", &["o!"], 1, 1, State::open_force),
-    Command::new("write", &["w"], 0, 1, State::write),
-    Command::new("quit", &["q"], 0, 0, State::quit),
-    Command::new("quit!", &["q!"], 0, 0, State::quit_force),
-    Command::new("write-quit", &["wq"], 0, 1, State::write_quit),
-    Command::new("brush", &["tile", "t"], 1, 1, State::brush),
-    Command::new("dot", &[], 0, 0, State::dot),
-    Command::new("bucket", &[], 0, 0, State::bucket),
-    Command::new("move", &[], 1, 2, State::r#move),
-    Command::new("pick", &[], 0, 0, State::pick),
-    Command::new("pen", &[], 1, 1, State::pen),
-    Command::new("edge", &[], 1, 1, State::edge),
-    Command::new("goto", &["g"], 2, 2, State::goto),
-    Command::new("select", &["s"], 1, 1, State::select),
-    Command::new("undo", &[], 0, 0, State::undo),
-    Command::new("redo", &[], 0, 0, State::redo),
-    Command::new("create", &["n"], 2, 2, State::create),
-    Command::new("box", &["b"], 4, 5, State::r#box),
-    Command::new("ellipse", &["e"], 4, 5, State::ellipse),
-    Command::new("fuzzy", &["f"], 0, 1, State::fuzzy),
-    Command::new("copy", &[], 0, 0, State::copy),
-    Command::new("paste", &[], 0, 0, State::paste),
-    Command::new("clipboard", &["c"], 1, 1, State::clipboard),
+const KEYBINDS: [Keybind; 35] = [
+    Keybind::new(&["h", "Left"], "move left", true),
+    Keybind::new(&["j", "Down"], "move down", true),
+    Keybind::new(&["k", "Up"], "move up", true),
+    Keybind::new(&["l", "Right"], "move right", true),
+    Keybind::new(&["H"], "edge left", true),
+    Keybind::new(&["J"], "edge down", true),
+    Keybind::new(&["K"], "edge up", true),
+    Keybind::new(&["L"], "edge right", true),
+    Keybind::new(&["d"], "dot", true),
+    Keybind::new(&["f"], "bucket", false),
+    Keybind::new(&["a"], "brush add", false),
+    Keybind::new(&["s"], "brush subtract", false),
+    Keybind::new(&["A"], "select all", false),
+    Keybind::new(&["S"], "select none", false),
+    Keybind::new(&["F"], "select invert", false),
+    Keybind::new(&["u"], "undo", true),
+    Keybind::new(&["U"], "redo", true),
+    Keybind::new(&["i"], "pen down", false),
+    Keybind::new(&["I"], "pen up", false),
+    Keybind::new(&["o"], "copy", false),
+    Keybind::new(&["O"], "paste", true),
+    Keybind::new(&["p"], "pick", false),
+    Keybind::new(&["m"], "lift", false),
+    Keybind::new(&["+"], "zoom in", false),
+    Keybind::new(&["-"], "zoom out", false),
+    Keybind::new(&["M"], "minimap", false),
+    Keybind::new(&["P"], "palette focus", false),
+    Keybind::new(&["G"], "glyphs", false),
+    Keybind::new(&["C-e"], "scroll down", true),
+    Keybind::new(&["C-y"], "scroll up", true),
+    Keybind::new(&["z h"], "scroll left", true),
+    Keybind::new(&["z l"], "scroll right", true),
+    Keybind::new(&["z z"], "center", false),
+    Keybind::new(&["."], "repeat", true),
+    Keybind::new(&["?"], "help", false),
+];
+
+const COMMANDS: [Command; 54] = [
+    Command::new(
+        "open",
+        &["o"],
+        1,
+        1,
+        "<path>",
+        "Opens a file to edit, fails if there are unsaved changes.",
+        State::open,
+    ),
+    Command::new(
+        "open!",
+        &["o!"],
+        1,
+        1,
+        "<path>",
+        "Opens a file to edit and discards unsaved changes.",
+        State::open_force,
+    ),
+    Command::new(
+        "write",
+        &["w"],
+        0,
+        1,
+        "<path>?",
+        "Saves the map to the path, or to the current path if none is given.",
+        State::write,
+    ),
+    Command::new(
+        "quit",
+        &["q"],
+        0,
+        0,
+        "",
+        "Exits the editor, fails if there are unsaved changes.",
+        State::quit,
+    ),
+    Command::new(
+        "quit!",
+        &["q!"],
+        0,
+        0,
+        "",
+        "Exits the editor and discards unsaved changes.",
+        State::quit_force,
+    ),
+    Command::new(
+        "write-quit",
+        &["wq"],
+        0,
+        1,
+        "<path>?",
+        "Saves the map and then exits the editor.",
+        State::write_quit,
+    ),
+    Command::new(
+        "source",
+        &["so"],
+        1,
+        1,
+        "<path>",
+        "Runs the commands in a file, one per line, skipping lines starting with #.",
+        State::source,
+    ),
+    Command::new(
+        "brush",
+        &["tile", "t"],
+        1,
+        1,
+        "add|subtract|<tile>",
+        "Sets the brush, tiles can be given by name or number.",
+        State::brush,
+    ),
+    Command::new(
+        "dot",
+        &[],
+        0,
+        1,
+        "<count>?",
+        "Draws at the cursor, or on as many tiles in the last direction moved.",
+        State::dot,
+    ),
+    Command::new(
+        "bucket",
+        &[],
+        0,
+        0,
+        "",
+        "Draws on the entire selection.",
+        State::bucket,
+    ),
+    Command::new(
+        "move",
+        &[],
+        1,
+        2,
+        "<direction> <distance>?",
+        "Moves the cursor.",
+        State::r#move,
+    ),
+    Command::new(
+        "pick",
+        &[],
+        0,
+        0,
+        "",
+        "Sets the brush to the tile under the cursor.",
+        State::pick,
+    ),
+    Command::new(
+        "pen",
+        &[],
+        1,
+        1,
+        "up|down",
+        "Sets the pen mode, while down moving the cursor draws.",
+        State::pen,
+    ),
+    Command::new(
+        "size",
+        &["z"],
+        1,
+        2,
+        "<radius> square|circle|diamond?",
+        "Sets the brush size used by dot and the pen.",
+        State::size,
+    ),
+    Command::new(
+        "edge",
+        &[],
+        1,
+        2,
+        "<direction> <parts>?",
+        "Moves the cursor to the border, or by that fraction of the map.",
+        State::edge,
+    ),
+    Command::new(
+        "goto",
+        &["g"],
+        2,
+        2,
+        "<x> <y>",
+        "Moves the cursor to the position.",
+        State::goto,
+    ),
+    Command::new(
+        "scroll",
+        &[],
+        1,
+        2,
+        "<direction> <distance>?",
+        "Scrolls the view, moving the cursor only if it would leave it.",
+        State::scroll,
+    ),
+    Command::new(
+        "center",
+        &[],
+        0,
+        0,
+        "",
+        "Scrolls the view so that the cursor is in the middle.",
+        State::center,
+    ),
+    Command::new(
+        "scrolloff",
+        &[],
+        1,
+        1,
+        "<margin>",
+        "Sets how many tiles the view keeps between the cursor and its edge.",
+        State::scrolloff,
+    ),
+    Command::new(
+        "select",
+        &["s"],
+        1,
+        1,
+        "all|none|invert|<tile>",
+        "Selects everything, nothing, the inverse or all tiles of a type.",
+        State::select,
+    ),
+    Command::new(
+        "undo",
+        &[],
+        0,
+        1,
+        "begin|end|<count>?",
+        "Undoes the last changes, or begins or ends a group of changes undone together.",
+        State::undo,
+    ),
+    Command::new(
+        "redo",
+        &[],
+        0,
+        1,
+        "<count>?",
+        "Redoes the last undone changes.",
+        State::redo,
+    ),
+    Command::new(
+        "earlier",
+        &[],
+        0,
+        1,
+        "<count>|<time>?",
+        "Goes back a number of changes or an amount of time like 30s, 5m, 2h or 1d.",
+        State::earlier,
+    ),
+    Command::new(
+        "later",
+        &[],
+        0,
+        1,
+        "<count>|<time>?",
+        "Goes forward a number of changes or an amount of time.",
+        State::later,
+    ),
+    Command::new(
+        "undolist",
+        &[],
+        0,
+        0,
+        "",
+        "Lists the branches of the undo tree.",
+        State::undolist,
+    ),
+    Command::new(
+        "undolimit",
+        &[],
+        1,
+        1,
+        "<megabytes>",
+        "Sets how much memory the undo history may use.",
+        State::undolimit,
+    ),
+    Command::new(
+        "create",
+        &["n"],
+        2,
+        2,
+        "<width> <height>",
+        "Creates a new empty map.",
+        State::create,
+    ),
+    Command::new(
+        "resize",
+        &[],
+        2,
+        4,
+        "<width> <height> <anchor>? <tile>?",
+        "Resizes the map, keeping its content at the anchor and filling new space with the tile.",
+        State::resize,
+    ),
+    Command::new(
+        "crop",
+        &[],
+        0,
+        0,
+        "",
+        "Crops the map to the bounding box of the selection.",
+        State::crop,
+    ),
+    Command::new(
+        "pad",
+        &[],
+        5,
+        5,
+        "<left> <top> <right> <bottom> <tile>",
+        "Adds rows and columns of the tile on each side of the map.",
+        State::pad,
+    ),
+    Command::new(
+        "map",
+        &[],
+        1,
+        2,
+        "rotate cw|ccw|180, flip h|v or transpose",
+        "Rotates, flips or transposes the whole map.",
+        State::map,
+    )
+    .completing("rotate|flip|transpose cw|ccw|180|h|v?"),
+    Command::new(
+        "box",
+        &["b"],
+        4,
+        5,
+        "<x0> <y0> <x1> <y1> fill?",
+        "Draws a rectangle, filled if fill is given.",
+        State::r#box,
+    ),
+    Command::new(
+        "ellipse",
+        &["e"],
+        4,
+        5,
+        "<x0> <y0> <x1> <y1> fill?",
+        "Draws an ellipse, filled if fill is given.",
+        State::ellipse,
+    ),
+    Command::new(
+        "fuzzy",
+        &["f"],
+        0,
+        3,
+        "<length>? diagonal? family?",
+        "Fills the tiles of the same type connected to the cursor.",
+        State::fuzzy,
+    ),
+    Command::new(
+        "copy",
+        &[],
+        0,
+        0,
+        "",
+        "Copies the selection to the clipboard.",
+        State::copy,
+    ),
+    Command::new(
+        "paste",
+        &[],
+        0,
+        1,
+        "<count>?",
+        "Pastes the clipboard at the cursor, repeatedly stepping by its size in the last direction moved.",
+        State::paste,
+    ),
+    Command::new(
+        "clipboard",
+        &["c"],
+        1,
+        1,
+        "c|a|h|v",
+        "Rotates the clipboard clockwise or anticlockwise, or reflects it.",
+        State::clipboard,
+    ),
+    Command::new(
+        "selection",
+        &[],
+        2,
+        3,
+        "rotate cw|ccw or flip h|v, then center|cursor?",
+        "Rotates or flips the selected tiles.",
+        State::selection,
+    )
+    .completing("rotate|flip cw|ccw|h|v center|cursor?"),
+    Command::new(
+        "background",
+        &["bg"],
+        1,
+        1,
+        "<tile>",
+        "Sets the tile left behind when moving tiles.",
+        State::background,
+    ),
+    Command::new(
+        "zoom",
+        &[],
+        1,
+        1,
+        "in|out|wide|square|half|<factor>",
+        "Sets how tiles are drawn.",
+        State::zoom,
+    ),
+    Command::new(
+        "minimap",
+        &[],
+        0,
+        1,
+        "on|off?",
+        "Shows or hides an overview of the whole map.",
+        State::minimap,
+    ),
+    Command::new(
+        "palette",
+        &[],
+        0,
+        1,
+        "on|off|focus?",
+        "Shows or hides the list of tiles, or focuses it to choose the brush.",
+        State::palette,
+    ),
+    Command::new(
+        "glyphs",
+        &[],
+        0,
+        1,
+        "on|off?",
+        "Shows or hides an abbreviation on every tile.",
+        State::glyphs,
+    ),
+    Command::new(
+        "rulers",
+        &[],
+        0,
+        1,
+        "on|off?",
+        "Shows or hides the row and column numbers.",
+        State::rulers,
+    ),
+    Command::new(
+        "grid",
+        &[],
+        1,
+        1,
+        "<spacing>",
+        "Darkens every row and column that is a multiple of the spacing, 0 turns it off.",
+        State::grid,
+    ),
+    Command::new(
+        "colors",
+        &[],
+        0,
+        1,
+        "truecolor|256|16|auto?",
+        "Sets how many colours are used, or shows the current setting.",
+        State::colors,
+    ),
+    Command::new(
+        "lift",
+        &[],
+        0,
+        0,
+        "",
+        "Lifts the selection into a floating layer.",
+        State::lift,
+    ),
+    Command::new(
+        "nudge",
+        &[],
+        1,
+        2,
+        "<direction> <distance>?",
+        "Moves the lifted tiles.",
+        State::nudge,
+    ),
+    Command::new(
+        "drop",
+        &[],
+        0,
+        0,
+        "",
+        "Drops the lifted tiles, leaving the background behind.",
+        State::drop,
+    ),
+    Command::new(
+        "cancel",
+        &[],
+        0,
+        0,
+        "",
+        "Puts the lifted tiles back without changing the map.",
+        State::cancel,
+    ),
+    Command::new(
+        "repeat",
+        &[],
+        0,
+        1,
+        "<count>?",
+        "Repeats the last dot, bucket, paste, box, ellipse or fuzzy at the cursor.",
+        State::repeat,
+    ),
+    Command::new(
+        "macro",
+        &[],
+        1,
+        2,
+        "save|<register> <register>|<keys>?",
+        "Shows or sets the keys of a macro, or saves it to the config file.",
+        State::r#macro,
+    ),
+    Command::new(
+        "history",
+        &[],
+        0,
+        0,
+        "",
+        "Lists the commands entered in the bar, newest first.",
+        State::command_history,
+    ),
+    Command::new(
+        "help",
+        &[],
+        0,
+        1,
+        "<command>?",
+        "Shows the commands and keys, or the details of one command.",
+        State::help,
+    ),
 ];
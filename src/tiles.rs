@@ -4,3 +4,16 @@ static_toml! {
     #[static_toml(values_ident = Tile)]
     pub(crate) const TILES = include_toml!("data/tiles.toml");
 }
+
+pub(crate) fn family(tile: i32) -> i32 {
+    let name = |tile: i32| TILES.tiles.iter().find(|t| t.0 as i32 == tile).map(|t| t.1);
+    let Some(name) = name(tile) else {
+        return tile;
+    };
+    TILES
+        .families
+        .iter()
+        .find(|f| f[0] == name)
+        .and_then(|f| TILES.tiles.iter().find(|t| t.1 == f[1]))
+        .map_or(tile, |t| t.0 as i32)
+}
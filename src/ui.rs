@@ -3,62 +3,157 @@ use std::io;
 use ratatui::{
     Frame,
     buffer::Buffer,
-    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    crossterm::event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind,
+    },
     layout::Rect,
     prelude::Color,
     style::Stylize,
-    widgets::{Paragraph, Widget},
+    text::{Line, Span},
+    widgets::{Block, Clear, Paragraph, Widget},
 };
 
+use itertools::Itertools;
+
 use crate::{
-    state::{Bar, CommandResult, State},
+    bar::Completion,
+    keys::key_name,
+    map::{in_bounds, line},
+    state::{Bar, Brush, CommandResult, State, Zoom, completions},
     tiles::TILES,
 };
 
 const SELECT_COLOR: Color = Color::Rgb(0, 0, 255);
 const CURSOR_COLOR: Color = Color::Rgb(255, 0, 0);
+const VIEWPORT_COLOR: Color = Color::Rgb(255, 255, 255);
+const MINIMAP_MIN: usize = 16;
+const SCROLL_LINES: &str = "3";
+const PALETTE_WIDTH: u16 = 25;
+const COMPLETION_HEIGHT: usize = 10;
+
+fn tile_color(tile: i32) -> Color {
+    Color::from_u32(TILES.tiles.iter().find(|t| t.0 as i32 == tile).unwrap().2 as u32)
+}
+
+fn tile_glyph(tile: i32) -> &'static str {
+    TILES.tiles.iter().find(|t| t.0 as i32 == tile).unwrap().3
+}
+
+fn square_glyph(glyph: &str) -> &str {
+    if TILES
+        .tiles
+        .iter()
+        .filter(|t| t.3[..1] == glyph[..1])
+        .count()
+        == 1
+    {
+        &glyph[..1]
+    } else {
+        " "
+    }
+}
+
+fn glyph_color(tile: i32) -> Color {
+    match tile_color(tile) {
+        Color::Rgb(r, g, b) if 299 * r as u32 + 587 * g as u32 + 114 * b as u32 > 128_000 => {
+            Color::Black
+        }
+        _ => Color::White,
+    }
+}
+
+fn shade(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => Color::Rgb(
+            (r as u16 * 3 / 4) as u8,
+            (g as u16 * 3 / 4) as u8,
+            (b as u16 * 3 / 4) as u8,
+        ),
+        _ => color,
+    }
+}
+
+fn digits(n: usize) -> usize {
+    n.max(1).ilog10() as usize + 1
+}
+
+fn mix(color: Color, other: Color) -> Color {
+    match (color, other) {
+        (Color::Rgb(r0, g0, b0), Color::Rgb(r1, g1, b1)) => Color::Rgb(
+            ((r0 as u16 + r1 as u16) / 2) as u8,
+            ((g0 as u16 + g1 as u16) / 2) as u8,
+            ((b0 as u16 + b1 as u16) / 2) as u8,
+        ),
+        _ => other,
+    }
+}
 
 impl State {
-    fn pixel(&self, x: usize, y: usize) -> Option<Paragraph> {
-        if x < self.map.map[0].len() + 2 && y < self.map.map.len() + 2 {
+    fn on_grid(&self, i: usize, j: usize, n: usize) -> bool {
+        let grid = self.grid;
+        grid > 0 && n < grid && (i.div_ceil(grid) * grid < i + n || j.div_ceil(grid) * grid < j + n)
+    }
+
+    fn outlined(&self, i: usize, j: usize) -> bool {
+        self.size > 0
+            && self.footprint.outline(
+                self.size,
+                i as isize - self.cursory as isize,
+                j as isize - self.cursorx as isize,
+            )
+    }
+
+    fn pixel(&self, x: usize, y: usize, wide: bool) -> Option<Paragraph<'_>> {
+        if x < self.map.map.width() + 2 && y < self.map.map.height() + 2 {
+            let text = |w, s| if wide { w } else { s };
             Some(
                 match (
                     x == 0,
                     y == 0,
-                    x == self.map.map[0].len() + 1,
-                    y == self.map.map.len() + 1,
+                    x == self.map.map.width() + 1,
+                    y == self.map.map.height() + 1,
                 ) {
-                    (true, true, _, _) => Paragraph::new("|-"),
-                    (true, _, _, true) => Paragraph::new("|-"),
-                    (_, true, true, _) => Paragraph::new("-|"),
-                    (_, _, true, true) => Paragraph::new("-|"),
-                    (false, true, false, _) | (false, _, false, true) => Paragraph::new("--"),
-                    (true, false, _, false) => Paragraph::new("| "),
-                    (_, false, true, false) => Paragraph::new(" |"),
+                    (true, true, _, _) => Paragraph::new(text("|-", "+")),
+                    (true, _, _, true) => Paragraph::new(text("|-", "+")),
+                    (_, true, true, _) => Paragraph::new(text("-|", "+")),
+                    (_, _, true, true) => Paragraph::new(text("-|", "+")),
+                    (false, true, false, _) | (false, _, false, true) => {
+                        Paragraph::new(text("--", "-"))
+                    }
+                    (true, false, _, false) => Paragraph::new(text("| ", "|")),
+                    (_, false, true, false) => Paragraph::new(text(" |", "|")),
                     _ => {
                         let j = x - 1;
                         let i = y - 1;
-                        let select = self.map.select.contains(&(i, j));
-                        Paragraph::new(if j == self.cursorx && i == self.cursory {
-                            "<>"
+                        let select = self.selected(i, j);
+                        let tile = self.tile_at(i, j);
+                        let marker = j == self.cursorx && i == self.cursory || self.outlined(i, j);
+                        let paragraph = Paragraph::new(if j == self.cursorx && i == self.cursory {
+                            text("<>", "X")
+                        } else if self.outlined(i, j) {
+                            text("[]", "+")
+                        } else if self.glyphs {
+                            let glyph = tile_glyph(tile);
+                            text(glyph, square_glyph(glyph))
                         } else if select {
-                            "\\\\"
+                            text("\\\\", "\\")
                         } else {
-                            "  "
+                            text("  ", " ")
                         })
-                        .bg(Color::from_u32(
-                            TILES
-                                .tiles
-                                .iter()
-                                .find(|t| t.0 as i32 == self.map.map[i][j])
-                                .unwrap()
-                                .2 as u32,
-                        ))
-                        .fg(if select {
-                            SELECT_COLOR
+                        .bg(if self.on_grid(i, j, 1) {
+                            shade(tile_color(tile))
                         } else {
-                            CURSOR_COLOR
-                        })
+                            tile_color(tile)
+                        });
+                        if self.glyphs && !marker {
+                            if select {
+                                paragraph.fg(SELECT_COLOR).underlined()
+                            } else {
+                                paragraph.fg(glyph_color(tile))
+                            }
+                        } else {
+                            paragraph.fg(if select { SELECT_COLOR } else { CURSOR_COLOR })
+                        }
                     }
                 },
             )
@@ -67,25 +162,289 @@ impl State {
         }
     }
 
+    fn block_color(&self, i: usize, j: usize, n: usize) -> Option<Color> {
+        let (x, y) = (self.map.map.height(), self.map.map.width());
+        if i >= x || j >= y {
+            return None;
+        }
+        let (i1, j1) = ((i + n).min(x), (j + n).min(y));
+        if (i..i1).contains(&self.cursory) && (j..j1).contains(&self.cursorx) {
+            return Some(CURSOR_COLOR);
+        }
+        let mut counts: Vec<(i32, usize)> = Vec::new();
+        let mut select = false;
+        for (ti, tj) in (i..i1).cartesian_product(j..j1) {
+            let tile = self.tile_at(ti, tj);
+            match counts.iter_mut().find(|(t, _)| *t == tile) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((tile, 1)),
+            }
+            select |= self.selected(ti, tj);
+        }
+        let color = tile_color(counts.iter().max_by_key(|(_, count)| *count).unwrap().0);
+        Some(if n == 1 && self.outlined(i, j) {
+            mix(color, CURSOR_COLOR)
+        } else if select {
+            mix(color, SELECT_COLOR)
+        } else {
+            color
+        })
+    }
+
+    fn render_blocks<F>(&self, area: Rect, buf: &mut Buffer, color: F)
+    where
+        F: Fn(u16, u16) -> Option<Color>,
+    {
+        for x in 0..area.width {
+            for y in 0..area.height {
+                let cell = &mut buf[(area.x + x, area.y + y)];
+                match (color(x, 2 * y), color(x, 2 * y + 1)) {
+                    (Some(top), bottom) => {
+                        cell.set_symbol("▀")
+                            .set_fg(top)
+                            .set_bg(bottom.unwrap_or(Color::Reset));
+                    }
+                    (None, Some(bottom)) => {
+                        cell.set_symbol("▄").set_fg(bottom).set_bg(Color::Reset);
+                    }
+                    (None, None) => (),
+                }
+            }
+        }
+    }
+
+    fn view_size(&self, area: Rect) -> (usize, usize) {
+        let (height, width) = (area.height as usize, area.width as usize);
+        match self.zoom {
+            Zoom::Wide => (height, width / 2),
+            Zoom::Square => (height, width),
+            Zoom::Blocks(n) => (
+                (2 * height).saturating_sub(1) * n,
+                width.saturating_sub(1) * n,
+            ),
+        }
+    }
+
+    fn viewport(&self, area: Rect) -> (isize, isize, usize, usize) {
+        let (rows, cols) = self.view_size(area);
+        match self.zoom {
+            Zoom::Wide | Zoom::Square => (self.cameray, self.camerax, rows, cols),
+            Zoom::Blocks(n) => {
+                let n = n as isize;
+                (
+                    self.cameray - self.cameray.rem_euclid(n),
+                    self.camerax - self.camerax.rem_euclid(n),
+                    rows,
+                    cols,
+                )
+            }
+        }
+    }
+
     fn render_map(&self, area: Rect, buf: &mut Buffer) {
-        let width = area.width / 2;
-        for x in 0..width.min(self.map.map[0].len() as u16 + 2) {
-            for y in 0..area.height.min(self.map.map.len() as u16 + 2) {
-                if let Some(pixel) = self.pixel(
-                    self.cursorx.saturating_sub(width as usize - 3) + x as usize,
-                    self.cursory.saturating_sub(area.height as usize - 3) + y as usize,
-                ) {
-                    pixel.render(Rect::new(area.x + 2 * x, area.y + y, 2, 1), buf);
+        let (i0, j0, _, _) = self.viewport(area);
+        match self.zoom {
+            Zoom::Wide | Zoom::Square => {
+                let cw = if self.zoom == Zoom::Wide { 2 } else { 1 };
+                let width = area.width / cw;
+                for x in 0..width.min(self.map.map.width() as u16 + 2) {
+                    for y in 0..area.height.min(self.map.map.height() as u16 + 2) {
+                        if let Some(pixel) = self.pixel(
+                            (j0 + 1) as usize + x as usize,
+                            (i0 + 1) as usize + y as usize,
+                            cw == 2,
+                        ) {
+                            pixel.render(Rect::new(area.x + cw * x, area.y + y, cw, 1), buf);
+                        }
+                    }
                 }
             }
+            Zoom::Blocks(n) => {
+                let (i0, j0) = (i0 as usize, j0 as usize);
+                self.render_blocks(area, buf, |x, y| {
+                    let (i, j) = (i0 + y as usize * n, j0 + x as usize * n);
+                    self.block_color(i, j, n).map(|color| {
+                        if color != CURSOR_COLOR && self.on_grid(i, j, n) {
+                            shade(color)
+                        } else {
+                            color
+                        }
+                    })
+                });
+            }
         }
     }
 
-    pub(crate) fn draw(&self, frame: &mut Frame) {
+    fn minimap_layout(&self, area: Rect) -> Option<(Rect, usize)> {
+        let (x, y) = (self.map.map.height(), self.map.map.width());
+        let (inner_w, inner_h) = (
+            (area.width as usize / 4).max(MINIMAP_MIN) - 2,
+            (area.height as usize / 2).max(MINIMAP_MIN / 2) - 2,
+        );
+        let n = y.div_ceil(inner_w).max(x.div_ceil(2 * inner_h)).max(1);
+        let (w, h) = (y.div_ceil(n) as u16 + 2, x.div_ceil(2 * n) as u16 + 2);
+        if !self.minimap || w > area.width || h > area.height {
+            None
+        } else {
+            Some((Rect::new(area.x + area.width - w, area.y, w, h), n))
+        }
+    }
+
+    fn render_minimap(&self, area: Rect, buf: &mut Buffer) {
+        let Some((minimap_area, n)) = self.minimap_layout(area) else {
+            return;
+        };
+        let block = Block::bordered().title("Minimap");
+        let inner = block.inner(minimap_area);
+        Clear.render(minimap_area, buf);
+        block.render(minimap_area, buf);
+
+        let (vi0, vj0, rows, cols) = self.viewport(area);
+        let (vi1, vj1) = (vi0 + rows as isize - 1, vj0 + cols as isize - 1);
+        let edge = |i: usize, j: usize| {
+            let (i0, j0, i1, j1) = (
+                i as isize,
+                j as isize,
+                (i + n) as isize - 1,
+                (j + n) as isize - 1,
+            );
+            let (rows, cols) = (i1 >= vi0 && i0 <= vi1, j1 >= vj0 && j0 <= vj1);
+            let (span_i, span_j) = (i0..=i1, j0..=j1);
+            rows && (span_j.contains(&vj0) || span_j.contains(&vj1))
+                || cols && (span_i.contains(&vi0) || span_i.contains(&vi1))
+        };
+        self.render_blocks(inner, buf, |bx, by| {
+            let (i, j) = (by as usize * n, bx as usize * n);
+            self.block_color(i, j, n).map(|color| {
+                if color != CURSOR_COLOR && edge(i, j) {
+                    mix(color, VIEWPORT_COLOR)
+                } else {
+                    color
+                }
+            })
+        });
+    }
+
+    fn render_palette(&self, area: Rect, buf: &mut Buffer) {
+        let Some(palette) = &self.palette else {
+            return;
+        };
+        let block = Block::bordered().title(if palette.focus {
+            "Palette (Esc)"
+        } else {
+            "Palette (P)"
+        });
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let brush = match self.brush {
+            Brush::Tile(tile) => Some(tile),
+            _ => None,
+        };
+        let hovered = self.map.map[(self.cursory, self.cursorx)];
+        let offset = palette.offset(inner.height as usize);
+        let lines = TILES
+            .tiles
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .map(|(index, t)| {
+                let tile = t.0 as i32;
+                let marker = if brush == Some(tile) {
+                    "*"
+                } else if hovered == tile {
+                    "+"
+                } else {
+                    " "
+                };
+                let line = Line::from(vec![
+                    Span::raw(marker),
+                    Span::raw("  ").bg(tile_color(tile)),
+                    Span::raw(format!(" {:>2} {} {}", tile, t.3, t.1)),
+                ]);
+                if palette.focus && index == palette.index {
+                    line.reversed()
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        Paragraph::new(lines).render(inner, buf);
+    }
+
+    fn render_rulers(&self, area: Rect, buf: &mut Buffer) {
+        let (i0, j0, _, _) = self.viewport(area);
+        let (top, left) = (area.y - 1, area.x - self.ruler_width());
+        let (x, y) = (self.map.map.height(), self.map.map.width());
+        let (tile_cols, tile_rows) = match self.zoom {
+            Zoom::Wide => (2.0, 1.0),
+            Zoom::Square => (1.0, 1.0),
+            Zoom::Blocks(n) => (1.0 / n as f64, 0.5 / n as f64),
+        };
+        let step = |per_tile: f64, space: usize| {
+            [1, 2, 5]
+                .iter()
+                .cycle()
+                .enumerate()
+                .map(|(k, base)| base * 10_usize.pow(k as u32 / 3))
+                .find(|step| *step as f64 * per_tile >= space as f64)
+                .unwrap()
+        };
+        let (step_j, step_i) = (step(tile_cols, digits(y) + 1), step(tile_rows, 1));
+        for j in (0..y).step_by(step_j) {
+            let offset = ((j as isize - j0) as f64 * tile_cols).floor();
+            if j as isize >= j0 && (offset as usize) < area.width as usize {
+                let label = j.to_string();
+                let width = (label.len() as u16).min(area.width - offset as u16);
+                Paragraph::new(label)
+                    .dark_gray()
+                    .render(Rect::new(area.x + offset as u16, top, width, 1), buf);
+            }
+        }
+        for i in (0..x).step_by(step_i) {
+            let offset = ((i as isize - i0) as f64 * tile_rows).floor();
+            if i as isize >= i0 && (offset as usize) < area.height as usize {
+                Paragraph::new(format!("{:>1$}", i, digits(x)))
+                    .dark_gray()
+                    .render(
+                        Rect::new(left, area.y + offset as u16, digits(x) as u16, 1),
+                        buf,
+                    );
+            }
+        }
+    }
+
+    fn ruler_width(&self) -> u16 {
+        digits(self.map.map.height()) as u16 + 1
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame) {
         let area = frame.area();
         let buf = frame.buffer_mut();
-        let map_area = Rect::new(0, 0, area.width, area.height - 2);
+        let mut map_area = Rect::new(0, 0, area.width, area.height - 2);
+        if self.palette.is_some() {
+            let width = PALETTE_WIDTH.min(area.width);
+            self.render_palette(Rect::new(0, 0, width, map_area.height), buf);
+            map_area.x += width;
+            map_area.width -= width;
+        }
+        let rulers = self.rulers && map_area.width > self.ruler_width() && map_area.height > 1;
+        if rulers {
+            let width = self.ruler_width();
+            map_area = Rect::new(
+                map_area.x + width,
+                map_area.y + 1,
+                map_area.width - width,
+                map_area.height - 1,
+            );
+        }
+        let (rows, cols) = self.view_size(map_area);
+        self.map_area = map_area;
+        self.follow(rows, cols);
         self.render_map(map_area, buf);
+        if rulers {
+            self.render_rulers(map_area, buf);
+        }
+        self.render_minimap(map_area, buf);
 
         let bar_area = Rect::new(0, area.height.max(1) - 1, area.width, 1);
         let info_area = Rect::new(0, area.height.max(2) - 2, area.width, 1);
@@ -105,19 +464,204 @@ impl State {
             }
             Bar::Closed => (),
         }
+        if let Some(popup) = &self.popup {
+            let popup_area = Rect::new(
+                area.width / 10,
+                area.height / 10,
+                area.width - area.width / 5,
+                (area.height - area.height / 5).saturating_sub(2),
+            );
+            let buf = frame.buffer_mut();
+            Clear.render(popup_area, buf);
+            Paragraph::new(popup.lines.join("\n"))
+                .scroll((popup.scroll as u16, 0))
+                .block(Block::bordered().title(format!(" {} (Esc to close) ", popup.title)))
+                .render(popup_area, buf);
+        }
+        if let Bar::Input(input) = &self.bar
+            && let Some(completion) = input.completion()
+        {
+            render_completion(completion, area, info_area.y, frame.buffer_mut());
+        }
+        self.colors.convert_buffer(frame.buffer_mut());
+    }
+
+    fn screen_to_tile(&self, column: u16, row: u16) -> Option<(usize, usize)> {
+        let area = self.map_area;
+        let inside = |area: Rect| {
+            (area.x..area.x + area.width).contains(&column)
+                && (area.y..area.y + area.height).contains(&row)
+        };
+        if !inside(area)
+            || self
+                .minimap_layout(area)
+                .is_some_and(|(minimap, _)| inside(minimap))
+        {
+            return None;
+        }
+        let (i0, j0, _, _) = self.viewport(area);
+        let (x, y) = ((row - area.y) as isize, (column - area.x) as isize);
+        let (i, j) = match self.zoom {
+            Zoom::Wide => (i0 + x, j0 + y / 2),
+            Zoom::Square => (i0 + x, j0 + y),
+            Zoom::Blocks(n) => (i0 + 2 * x * n as isize, j0 + y * n as isize),
+        };
+        if i >= 0
+            && j >= 0
+            && in_bounds(
+                self.map.map.height(),
+                self.map.map.width(),
+                i as usize,
+                j as usize,
+            )
+        {
+            Some((i as usize, j as usize))
+        } else {
+            None
+        }
+    }
+
+    fn pick_half(&self, (i, j): (usize, usize), near: (usize, usize)) -> (usize, usize) {
+        let Zoom::Blocks(n) = self.zoom else {
+            return (i, j);
+        };
+        let lower = i + n;
+        if lower >= self.map.map.height() || near == (lower, j) {
+            (i, j)
+        } else if near == (i, j) || near.0 > i + n / 2 {
+            (lower, j)
+        } else {
+            (i, j)
+        }
+    }
+
+    fn receive_mouse_palette(&mut self, event: MouseEvent) {
+        let height = (self.map_area.y + self.map_area.height).saturating_sub(2);
+        let Some(palette) = &mut self.palette else {
+            return;
+        };
+        match event.kind {
+            MouseEventKind::ScrollDown => palette.down(),
+            MouseEventKind::ScrollUp => palette.up(),
+            MouseEventKind::Down(MouseButton::Left) if (1..=height).contains(&event.row) => {
+                let index = palette.offset(height as usize) + (event.row - 1) as usize;
+                if index < TILES.tiles.len() {
+                    palette.index = index;
+                    self.brush = Brush::Tile(palette.tile());
+                }
+            }
+            _ => (),
+        }
     }
 
-    pub(crate) fn handle_events(&mut self) -> Result<(), io::Error> {
+    fn receive_mouse(&mut self, event: MouseEvent) {
+        if let Some(popup) = &mut self.popup {
+            match event.kind {
+                MouseEventKind::ScrollDown => popup.scroll_down(),
+                MouseEventKind::ScrollUp => popup.scroll_up(),
+                _ => (),
+            }
+            return;
+        }
+        if self.floating.is_some() || matches!(self.bar, Bar::Input(_)) {
+            return;
+        }
+        if self.palette.is_some() && event.column < PALETTE_WIDTH {
+            self.receive_mouse_palette(event);
+            return;
+        }
+        let tile = self.screen_to_tile(event.column, event.row);
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if self.drag.take().is_some() {
+                    self.end();
+                }
+                if let Some(p) = tile {
+                    let (i, j) = self.pick_half(p, (self.cursory, self.cursorx));
+                    (self.cursory, self.cursorx) = (i, j);
+                    self.drag = Some((i, j));
+                    self.begin();
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some((i0, j0)) = self.drag
+                    && let Some(p) = tile
+                {
+                    let (i, j) = self.pick_half(p, (i0, j0));
+                    self.paint(&line(i0, j0, i, j));
+                    (self.cursory, self.cursorx) = (i, j);
+                    self.drag = Some((i, j));
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) if self.drag.is_some() => {
+                self.drag = None;
+                self.end();
+            }
+            MouseEventKind::Down(MouseButton::Right) => {
+                if let Some(p) = tile {
+                    self.brush = Brush::Tile(self.map.map[p]);
+                }
+            }
+            MouseEventKind::ScrollDown => {
+                self.scroll(&["down", SCROLL_LINES]);
+            }
+            MouseEventKind::ScrollUp => {
+                self.scroll(&["up", SCROLL_LINES]);
+            }
+            MouseEventKind::ScrollRight => {
+                self.scroll(&["right", SCROLL_LINES]);
+            }
+            MouseEventKind::ScrollLeft => {
+                self.scroll(&["left", SCROLL_LINES]);
+            }
+            _ => (),
+        }
+    }
+
+    pub fn handle_events(&mut self) -> Result<(), io::Error> {
         match event::read()? {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                self.receive_key(key_event.code)
+                self.receive_key(key_event)
             }
+            Event::Mouse(mouse_event) => self.receive_mouse(mouse_event),
             _ => (),
         };
+        self.commit();
         Ok(())
     }
 
-    fn receive_key(&mut self, code: KeyCode) {
+    pub(crate) fn receive_key(&mut self, key: KeyEvent) {
+        if self.playing == 0
+            && let Some((_, keys)) = &mut self.recording
+        {
+            keys.push(key);
+        }
+        let code = key.code;
+        if let Some(popup) = &mut self.popup {
+            match code {
+                KeyCode::Char('j') | KeyCode::Down => popup.scroll_down(),
+                KeyCode::Char('k') | KeyCode::Up => popup.scroll_up(),
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => self.popup = None,
+                _ => (),
+            }
+            return;
+        }
+        if let Some(palette) = &mut self.palette
+            && palette.focus
+        {
+            match code {
+                KeyCode::Char('j') | KeyCode::Down => palette.down(),
+                KeyCode::Char('k') | KeyCode::Up => palette.up(),
+                KeyCode::Enter => {
+                    self.brush = Brush::Tile(palette.tile());
+                    palette.focus = false;
+                }
+                KeyCode::Esc => palette.focus = false,
+                KeyCode::Char('P') | KeyCode::Char('q') => self.palette = None,
+                _ => (),
+            }
+            return;
+        }
         match &mut self.bar {
             Bar::Input(input) => match &code {
                 KeyCode::Right => input.move_right(),
@@ -125,9 +669,16 @@ impl State {
                 KeyCode::Char(c) => input.write(*c),
                 KeyCode::Backspace => input.backspace(),
                 KeyCode::Delete => input.delete(),
+                KeyCode::Tab => input.complete(true, completions),
+                KeyCode::BackTab => input.complete(false, completions),
                 KeyCode::Esc => self.bar = Bar::Closed,
+                KeyCode::Up => input.older(&self.commands),
+                KeyCode::Down => input.newer(&self.commands),
                 KeyCode::Enter => {
                     let text = input.text();
+                    if self.playing == 0 {
+                        self.commands.push(&text);
+                    }
                     match self.parse_command(&text) {
                         CommandResult::Err(err) => self.bar = Bar::Err(err),
                         CommandResult::Ok(message) => self.bar = Bar::Ok(message),
@@ -136,11 +687,48 @@ impl State {
                 }
                 _ => (),
             },
-            Bar::Closed => self.receive_key_closed(code),
-            Bar::Err(_) | Bar::Ok(_) => {
+            Bar::Closed | Bar::Err(_) | Bar::Ok(_) => {
                 self.bar = Bar::Closed;
-                self.receive_key_closed(code);
+                if let Some(name) = key_name(key) {
+                    self.receive_key_closed(code, name);
+                }
             }
         }
     }
 }
+
+fn render_completion(completion: &Completion, area: Rect, bottom: u16, buf: &mut Buffer) {
+    let width = completion
+        .candidates
+        .iter()
+        .map(String::len)
+        .max()
+        .unwrap_or(0) as u16
+        + 2;
+    let height = completion.candidates.len().min(COMPLETION_HEIGHT) as u16 + 2;
+    let popup_area = Rect::new(
+        (completion.start as u16 + 1).min(area.width.saturating_sub(width)),
+        bottom.saturating_sub(height),
+        width.min(area.width),
+        height.min(bottom),
+    );
+    let selected = completion.selected.unwrap_or(0);
+    let lines = completion
+        .candidates
+        .iter()
+        .enumerate()
+        .skip((selected + 1).saturating_sub(COMPLETION_HEIGHT))
+        .map(|(index, candidate)| {
+            let line = Line::raw(candidate.as_str());
+            if completion.selected == Some(index) {
+                line.reversed()
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>();
+    Clear.render(popup_area, buf);
+    Paragraph::new(lines)
+        .block(Block::bordered())
+        .render(popup_area, buf);
+}